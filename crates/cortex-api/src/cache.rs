@@ -1,2 +1,123 @@
+use std::convert::Infallible;
+use std::fmt;
+
+/// Reason a value could not be stored by [`Cache::put`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PutError {
+    /// There is not enough free space left for the entry.
+    Full,
+    /// The key or value is larger than the cache can store at all.
+    TooLarge,
+    /// The cache's value filter refused the value.
+    Rejected,
+}
+
+impl fmt::Display for PutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full => f.write_str("cache is full"),
+            Self::TooLarge => f.write_str("entry is too large to cache"),
+            Self::Rejected => f.write_str("value filter rejected the entry"),
+        }
+    }
+}
+
+impl std::error::Error for PutError {}
+
 /// Primary cache interface.
-pub trait Cache {}
+///
+/// Keys and values are byte strings. Values are returned by copy, since the
+/// stored bytes may move or be evicted as soon as the call returns.
+pub trait Cache {
+    /// Copy out the value stored under `key`.
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>>;
+
+    /// Store `value` under `key`, replacing any previous value.
+    ///
+    /// A failed put leaves any previous value in place. The error says why:
+    ///
+    /// - [`PutError::Full`]: there is no room for the entry right now, even
+    ///   counting the space the previous value would free. Freeing other
+    ///   entries may make room.
+    /// - [`PutError::TooLarge`]: the entry exceeds what the cache can ever
+    ///   hold, so retrying cannot help.
+    /// - [`PutError::Rejected`]: the value filter refused the value.
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<(), PutError>;
+
+    /// Remove the value stored under `key`, returning whether there was one.
+    fn remove(&self, key: &[u8]) -> bool;
+
+    /// Remove every entry for which `keep(key, value)` returns `false`.
+    ///
+    /// Values are passed as [`Cache::get`] would return them, after any value
+    /// filter. Entries that have expired are treated as already gone: they
+    /// are removed without being passed to `keep`. Implementations may hold
+    /// locks while calling `keep`, so it must not use the cache.
+    fn retain<F>(&self, keep: F)
+    where
+        F: Fn(&[u8], &[u8]) -> bool;
+
+    /// Call `f` with every live entry, in no particular order.
+    ///
+    /// Values are passed as for [`Cache::retain`], and expired entries are
+    /// skipped. `f` must not use the cache.
+    fn for_each<F>(&self, f: F)
+    where
+        F: FnMut(&[u8], &[u8]);
+
+    /// Look up every key in `keys`, returning the results in the same order.
+    ///
+    /// The default calls [`Cache::get`] for each key; implementations that
+    /// lock should override it to take each lock once per batch.
+    fn get_many<K: AsRef<[u8]>>(&self, keys: &[K]) -> Vec<Option<Vec<u8>>> {
+        keys.iter().map(|key| self.get(key.as_ref())).collect()
+    }
+
+    /// Store every key-value pair in `items`, returning one result per item
+    /// in the same order.
+    ///
+    /// A failed item does not stop the rest of the batch. The default calls
+    /// [`Cache::put`] for each item; implementations that lock should
+    /// override it to take each lock once per batch.
+    fn put_many<K, V, I>(&self, items: I) -> Vec<Result<(), PutError>>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        items
+            .into_iter()
+            .map(|(key, value)| self.put(key.as_ref(), value.as_ref()))
+            .collect()
+    }
+
+    /// Return the value stored under `key`, computing and inserting it with
+    /// `f` on a miss.
+    ///
+    /// The lookup and insert are atomic with respect to other callers, so `f`
+    /// runs at most once however many threads race on the same cold key, and
+    /// never on a hit. If the computed value cannot be stored it is still
+    /// returned.
+    ///
+    /// To get that guarantee, implementations may hold a lock covering `key`
+    /// while `f` runs. `f` must therefore not call back into the same cache:
+    /// touching a key guarded by the same lock deadlocks.
+    fn get_or_insert_with<F>(&self, key: &[u8], f: F) -> Vec<u8>
+    where
+        F: FnOnce() -> Vec<u8>,
+    {
+        match self.try_get_or_insert_with(key, || Ok::<_, Infallible>(f())) {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Like [`Cache::get_or_insert_with`], but `f` may fail.
+    ///
+    /// An error from `f` is passed through and nothing is inserted. The same
+    /// restriction applies: `f` may run under the cache's lock and must not
+    /// call back into the cache.
+    fn try_get_or_insert_with<F, E>(&self, key: &[u8], f: F) -> Result<Vec<u8>, E>
+    where
+        F: FnOnce() -> Result<Vec<u8>, E>;
+}
@@ -1,2 +1,60 @@
 /// Policy for evicting entries from the cache.
-pub trait EvictionPolicy {}
+///
+/// The cache reports entry lifecycle events to the policy and asks it for a
+/// victim whenever it needs room. `K` is whatever the cache uses to identify
+/// an entry. Event hooks default to doing nothing, for policies that do not
+/// care about a given event.
+pub trait EvictionPolicy<K> {
+    /// Start tracking `key`, which was just inserted.
+    fn on_insert(&mut self, _key: K) {}
+
+    /// Record an access to `key`.
+    fn on_access(&mut self, _key: &K) {}
+
+    /// Stop tracking `key`, which was removed from the cache by other means.
+    fn on_remove(&mut self, _key: &K) {}
+
+    /// Choose the next victim and stop tracking it.
+    ///
+    /// Returns `None` when the policy has nothing to evict.
+    fn evict(&mut self) -> Option<K>;
+
+    /// Choose up to `n` distinct victims and stop tracking all of them.
+    ///
+    /// Victims are returned in eviction order; fewer than `n` are returned
+    /// when the policy runs out. The default calls [`EvictionPolicy::evict`]
+    /// repeatedly; policies that can pick several victims more cheaply in one
+    /// pass should override it.
+    fn evict_batch(&mut self, n: usize) -> Vec<K> {
+        (0..n).map_while(|_| self.evict()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Evicts keys in insertion order, relying on the default batch method.
+    struct Fifo(Vec<u32>);
+
+    impl EvictionPolicy<u32> for Fifo {
+        fn on_insert(&mut self, key: u32) {
+            self.0.push(key);
+        }
+
+        fn evict(&mut self) -> Option<u32> {
+            (!self.0.is_empty()).then(|| self.0.remove(0))
+        }
+    }
+
+    #[test]
+    fn default_batch_stops_when_policy_runs_out() {
+        let mut fifo = Fifo(Vec::new());
+        for key in 1..=3 {
+            fifo.on_insert(key);
+        }
+        assert_eq!(fifo.evict_batch(2), vec![1, 2]);
+        assert_eq!(fifo.evict_batch(5), vec![3]);
+        assert!(fifo.0.is_empty());
+    }
+}
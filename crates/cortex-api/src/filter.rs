@@ -1,2 +1,45 @@
+use std::fmt;
+
+/// Error returned when a [`ValueFilter`] rejects a value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FilterError {
+    message: String,
+}
+
+impl FilterError {
+    /// Create an error with a human-readable reason.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for FilterError {}
+
 /// Filter for determining whether a value is valid.
-pub trait ValueFilter {}
+///
+/// Filters transform values on their way into the cache with
+/// [`ValueFilter::store`] and back out with [`ValueFilter::load`], and may
+/// reject either direction. `load` must undo `store`. Both hooks default to
+/// passing the value through unchanged.
+pub trait ValueFilter {
+    /// Transform `value` into the bytes to be stored.
+    fn store(&self, value: Vec<u8>) -> Result<Vec<u8>, FilterError> {
+        Ok(value)
+    }
+
+    /// Recover the original value from `stored` bytes.
+    fn load(&self, stored: Vec<u8>) -> Result<Vec<u8>, FilterError> {
+        Ok(stored)
+    }
+}
+
+/// The unit filter stores values unchanged.
+impl ValueFilter for () {}
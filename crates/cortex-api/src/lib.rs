@@ -7,6 +7,6 @@ mod cache;
 mod eviction;
 mod filter;
 
-pub use cache::Cache;
+pub use cache::{Cache, PutError};
 pub use eviction::EvictionPolicy;
-pub use filter::ValueFilter;
+pub use filter::{FilterError, ValueFilter};
@@ -0,0 +1,182 @@
+//! LZ4 compression as a [`ValueFilter`].
+//!
+//! Every stored value starts with a one-byte tag recording how the rest was
+//! encoded:
+//!
+//! ```text
+//! [ 0x00 ][ raw bytes ]
+//! [ 0x01 ][ uncompressed length (4 bytes, LE) ][ LZ4 block ]
+//! ```
+//!
+//! Values are only kept compressed when that is smaller than storing them
+//! raw, so incompressible data costs exactly one extra byte.
+
+use cortex_api::{FilterError, ValueFilter};
+
+/// Tag for a value stored as-is.
+const TAG_RAW: u8 = 0x00;
+/// Tag for a value stored as a size-prefixed LZ4 block.
+const TAG_LZ4: u8 = 0x01;
+/// Upper bound on the LZ4 expansion ratio, used to reject corrupt lengths
+/// before allocating for them.
+const MAX_RATIO: usize = 255;
+
+/// Compresses values on store and decompresses them on load.
+///
+/// Compression wraps the inner filter: it is applied after the inner filter's
+/// `store` and undone before the inner filter's `load`.
+#[derive(Clone, Debug, Default)]
+pub struct CompressFilter<F = ()> {
+    inner: F,
+}
+
+impl<F: ValueFilter> CompressFilter<F> {
+    /// Wrap `inner` with compression.
+    pub fn new(inner: F) -> Self {
+        Self { inner }
+    }
+
+    /// The wrapped filter.
+    pub fn inner(&self) -> &F {
+        &self.inner
+    }
+}
+
+impl<F: ValueFilter> ValueFilter for CompressFilter<F> {
+    fn store(&self, value: Vec<u8>) -> Result<Vec<u8>, FilterError> {
+        let value = self.inner.store(value)?;
+        let compressed = lz4_flex::block::compress_prepend_size(&value);
+        let (tag, body) = if compressed.len() < value.len() {
+            (TAG_LZ4, compressed)
+        } else {
+            (TAG_RAW, value)
+        };
+        let mut stored = Vec::with_capacity(1 + body.len());
+        stored.push(tag);
+        stored.extend_from_slice(&body);
+        Ok(stored)
+    }
+
+    fn load(&self, stored: Vec<u8>) -> Result<Vec<u8>, FilterError> {
+        let value = match stored.split_first() {
+            Some((&TAG_RAW, raw)) => raw.to_vec(),
+            Some((&TAG_LZ4, body)) => {
+                let len = body
+                    .first_chunk::<4>()
+                    .map(|len| u32::from_le_bytes(*len) as usize)
+                    .ok_or_else(|| FilterError::new("truncated compressed value"))?;
+                if len > body.len().saturating_mul(MAX_RATIO) {
+                    return Err(FilterError::new("corrupt compressed value length"));
+                }
+                lz4_flex::block::decompress_size_prepended(body)
+                    .map_err(|err| FilterError::new(format!("corrupt compressed value: {err}")))?
+            }
+            Some((tag, _)) => {
+                return Err(FilterError::new(format!(
+                    "unknown compression tag {tag:#04x}"
+                )));
+            }
+            None => return Err(FilterError::new("empty stored value")),
+        };
+        self.inner.load(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Shard;
+    use cortex_api::Cache;
+
+    /// Reverses values, to check the order filters are applied in.
+    struct Reverse;
+
+    impl ValueFilter for Reverse {
+        fn store(&self, mut value: Vec<u8>) -> Result<Vec<u8>, FilterError> {
+            value.reverse();
+            Ok(value)
+        }
+
+        fn load(&self, stored: Vec<u8>) -> Result<Vec<u8>, FilterError> {
+            self.store(stored)
+        }
+    }
+
+    #[test]
+    fn compressible_value_fits_only_after_compression() {
+        let json: Vec<u8> = br#"{"id":42,"tags":["a","b"],"ok":true},"#.repeat(60);
+        assert!(Shard::new(512).put(b"doc", &json).is_err());
+
+        let shard = Shard::with_filter(512, CompressFilter::new(Reverse));
+        shard.put(b"doc", &json).expect("fits compressed");
+        assert_eq!(shard.get(b"doc"), Some(json.clone()));
+
+        let mut bytes = Vec::new();
+        shard.snapshot(&mut bytes).expect("snapshot");
+        let (restored, stats) =
+            Shard::restore_with_filter(&mut &bytes[..], 512, CompressFilter::new(Reverse))
+                .expect("restore");
+        assert_eq!(stats.restored, 1);
+        assert_eq!(restored.get(b"doc"), Some(json));
+    }
+
+    #[test]
+    fn large_compressed_value_survives_snapshot() {
+        // Over the 64 KiB a slab entry can hold, but compressible.
+        let big: Vec<u8> = (0..100_000u32).map(|i| (i % 7) as u8).collect();
+        let shard = Shard::with_filter(64 * 1024, CompressFilter::new(()));
+        shard.put(b"big", &big).expect("fits compressed");
+        shard.put(b"small", b"value").expect("put");
+        let mut bytes = Vec::new();
+        shard.snapshot(&mut bytes).expect("snapshot");
+
+        let (restored, stats) =
+            Shard::restore_with_filter(&mut &bytes[..], 64 * 1024, CompressFilter::new(()))
+                .expect("restore");
+        assert_eq!((stats.restored, stats.rejected), (2, 0));
+        assert_eq!(restored.get(b"big"), Some(big));
+
+        // Without compression the big value no longer fits, which costs only
+        // that entry.
+        let (plain, stats) = Shard::restore(&mut &bytes[..], 64 * 1024).expect("restore");
+        assert_eq!((stats.restored, stats.rejected), (1, 1));
+        assert_eq!(plain.get(b"small").as_deref(), Some(&b"value"[..]));
+    }
+
+    #[test]
+    fn incompressible_value_takes_raw_path() {
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let random: Vec<u8> = (0..300)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+
+        let filter = CompressFilter::new(());
+        let stored = filter.store(random.clone()).expect("store");
+        assert_eq!(stored[0], TAG_RAW);
+        assert_eq!(stored.len(), random.len() + 1);
+        assert_eq!(filter.load(stored).expect("load"), random);
+    }
+
+    #[test]
+    fn corrupt_input_is_rejected() {
+        let filter = CompressFilter::new(());
+        let mut stored = filter.store(vec![b'x'; 1_000]).expect("store");
+        assert_eq!(stored[0], TAG_LZ4);
+
+        let mut truncated = stored.clone();
+        truncated.truncate(stored.len() - 2);
+        assert!(filter.load(truncated).is_err());
+        assert!(filter.load(stored[..3].to_vec()).is_err());
+        assert!(filter.load(Vec::new()).is_err());
+        assert!(filter.load(vec![0x7f, 1, 2]).is_err());
+
+        // A length claiming far more output than the block could produce.
+        stored[1..5].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(filter.load(stored).is_err());
+    }
+}
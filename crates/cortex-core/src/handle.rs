@@ -1,6 +1,13 @@
-//! Internal handle referencing a slab block.
+//! Opaque handle referencing a slab block.
 //!
-//! Exposed within the crate for coordinating slab access without leaking
-//! indices outside the module boundary.
+//! Returned by the slab so callers can refer to a block without the block
+//! index leaking outside the crate boundary. The handle also records the
+//! block's generation when it was issued, so the slab can reject it once the
+//! block has been freed and reused.
+
+/// Handle to an allocated slab block.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub(crate) struct Handle(pub(crate) usize);
+pub struct Handle {
+    pub(crate) index: usize,
+    pub(crate) generation: u16,
+}
@@ -0,0 +1,53 @@
+//! Key hashing shared by shard selection and the index.
+//!
+//! This is FxHash, the multiply-rotate hash used inside rustc: fast on short
+//! keys, deterministic across runs and platforms, and not resistant to keys
+//! chosen to collide. As in rustc's `Hash` for byte slices, the length is
+//! hashed first, then the bytes as little-endian words. Without the length,
+//! zero-extending the tail would make `b"a"` and `b"a\0"`, or all keys of
+//! zero bytes, hash alike.
+//!
+//! A bare multiply only carries entropy upwards: the low bits of the result
+//! depend only on the low bits of the last word, so keys differing in their
+//! leading bytes would share low bits. The result is therefore finished with
+//! MurmurHash3's `fmix64`, after which every output bit depends on every
+//! input bit and both the high bits (shard selection) and the low bits
+//! (index buckets) are usable.
+
+/// Multiplier from FxHash (`rustc-hash`).
+const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+fn add_word(hash: u64, word: u64) -> u64 {
+    (hash.rotate_left(5) ^ word).wrapping_mul(SEED)
+}
+
+/// Hash `key` with FxHash.
+pub(crate) fn fx_hash(key: &[u8]) -> u64 {
+    let mut hash = add_word(0, key.len() as u64);
+    let mut chunks = key.chunks_exact(8);
+    for chunk in &mut chunks {
+        hash = add_word(hash, u64::from_le_bytes(chunk.try_into().expect("8 bytes")));
+    }
+    let mut rest = chunks.remainder();
+    if let Some((word, tail)) = rest.split_first_chunk::<4>() {
+        hash = add_word(hash, u32::from_le_bytes(*word).into());
+        rest = tail;
+    }
+    if let Some((word, tail)) = rest.split_first_chunk::<2>() {
+        hash = add_word(hash, u16::from_le_bytes(*word).into());
+        rest = tail;
+    }
+    if let Some(&byte) = rest.first() {
+        hash = add_word(hash, byte.into());
+    }
+    fmix64(hash)
+}
+
+/// MurmurHash3's 64-bit finalizer.
+fn fmix64(mut hash: u64) -> u64 {
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
@@ -1,2 +1,190 @@
+use crate::Handle;
+use crate::hash::fx_hash;
+
+/// Bucket count of a new index.
+const INITIAL_BUCKETS: usize = 16;
+/// Load factor above which a new index doubles its bucket count.
+const DEFAULT_MAX_LOAD_FACTOR: f64 = 0.75;
+
 /// Index structure for fast lookups.
-pub struct Index;
+///
+/// Maps each key to the handle of the slab entry holding it, in a hash table
+/// of separately chained buckets. Buckets are picked from the low bits of the
+/// key's FxHash; [`ShardSet`](crate::ShardSet) routes on the high bits, so
+/// keys sharing a shard still spread over its buckets.
+///
+/// Once an insert pushes [`Index::load_factor`] above the configured maximum,
+/// the bucket count doubles and every entry is rehashed. Only the index
+/// moves; the handles, and the slab they point into, are untouched.
+#[derive(Debug)]
+pub struct Index {
+    buckets: Vec<Vec<(Box<[u8]>, Handle)>>,
+    len: usize,
+    max_load_factor: f64,
+}
+
+impl Index {
+    /// Maximum load factor that disables automatic resizing.
+    pub const NO_RESIZE: f64 = f64::INFINITY;
+
+    /// Create an empty index that resizes above a load factor of 0.75.
+    pub fn new() -> Self {
+        Self::with_max_load_factor(DEFAULT_MAX_LOAD_FACTOR)
+    }
+
+    /// Create an empty index that resizes once its load factor exceeds
+    /// `max_load_factor`, or never for [`Index::NO_RESIZE`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_load_factor` is not positive.
+    pub fn with_max_load_factor(max_load_factor: f64) -> Self {
+        assert!(max_load_factor > 0.0, "max load factor must be positive");
+        Self {
+            buckets: (0..INITIAL_BUCKETS).map(|_| Vec::new()).collect(),
+            len: 0,
+            max_load_factor,
+        }
+    }
+
+    /// Number of keys in the index.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the index holds no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of buckets, always a power of two.
+    pub fn bucket_count(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Keys per bucket.
+    pub fn load_factor(&self) -> f64 {
+        self.len as f64 / self.buckets.len() as f64
+    }
+
+    /// Handle stored under `key`.
+    pub fn get(&self, key: &[u8]) -> Option<Handle> {
+        self.buckets[self.bucket_of(key)]
+            .iter()
+            .find(|(stored, _)| **stored == *key)
+            .map(|&(_, handle)| handle)
+    }
+
+    /// Store `handle` under `key`, returning the handle it replaced.
+    pub fn insert(&mut self, key: &[u8], handle: Handle) -> Option<Handle> {
+        let bucket = self.bucket_of(key);
+        let chain = &mut self.buckets[bucket];
+        if let Some((_, stored)) = chain.iter_mut().find(|(stored, _)| **stored == *key) {
+            return Some(std::mem::replace(stored, handle));
+        }
+        chain.push((key.into(), handle));
+        self.len += 1;
+        // The entry is fully linked in before rehashing, so it moves with the
+        // rest.
+        if self.load_factor() > self.max_load_factor {
+            self.grow();
+        }
+        None
+    }
+
+    /// Iterate over every key and its handle, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], Handle)> {
+        self.buckets
+            .iter()
+            .flatten()
+            .map(|(key, handle)| (&**key, *handle))
+    }
+
+    /// Remove `key`, returning its handle.
+    pub fn remove(&mut self, key: &[u8]) -> Option<Handle> {
+        let bucket = self.bucket_of(key);
+        let chain = &mut self.buckets[bucket];
+        let position = chain.iter().position(|(stored, _)| **stored == *key)?;
+        self.len -= 1;
+        Some(chain.swap_remove(position).1)
+    }
+
+    fn bucket_of(&self, key: &[u8]) -> usize {
+        fx_hash(key) as usize & (self.buckets.len() - 1)
+    }
+
+    /// Double the bucket count and rehash every entry into the new table.
+    fn grow(&mut self) {
+        let count = self.buckets.len() * 2;
+        let old = std::mem::replace(&mut self.buckets, (0..count).map(|_| Vec::new()).collect());
+        for (key, handle) in old.into_iter().flatten() {
+            let bucket = self.bucket_of(&key);
+            self.buckets[bucket].push((key, handle));
+        }
+    }
+}
+
+impl Default for Index {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handle(index: usize) -> Handle {
+        Handle {
+            index,
+            generation: index as u16,
+        }
+    }
+
+    #[test]
+    fn resizing_preserves_every_mapping() {
+        let mut index = Index::new();
+        assert_eq!(index.bucket_count(), INITIAL_BUCKETS);
+        for i in 0..100 {
+            assert_eq!(index.insert(format!("key-{i}").as_bytes(), handle(i)), None);
+            assert!(index.load_factor() <= 0.75);
+        }
+        // 16 -> 32 -> 64 -> 128 -> 256.
+        assert_eq!(index.bucket_count(), 256);
+        assert_eq!(index.len(), 100);
+        for i in 0..100 {
+            assert_eq!(index.get(format!("key-{i}").as_bytes()), Some(handle(i)));
+        }
+        assert_eq!(index.iter().count(), 100);
+
+        assert_eq!(index.insert(b"key-7", handle(700)), Some(handle(7)));
+        assert_eq!(index.remove(b"key-7"), Some(handle(700)));
+        assert_eq!(index.get(b"key-7"), None);
+        assert_eq!(index.len(), 99);
+    }
+
+    #[test]
+    fn big_endian_keys_spread_over_buckets() {
+        let mut index = Index::new();
+        for i in 0..10_000u64 {
+            index.insert(&i.to_be_bytes(), handle(i as usize));
+        }
+        for i in 0..10_000u32 {
+            index.insert(&(i << 8).to_be_bytes(), handle(i as usize));
+        }
+        let longest = index.buckets.iter().map(Vec::len).max().unwrap_or(0);
+        assert!(longest <= 10, "longest chain has {longest} entries");
+        assert_eq!(index.get(&9_999u64.to_be_bytes()), Some(handle(9_999)));
+    }
+
+    #[test]
+    fn no_resize_sentinel_keeps_bucket_count() {
+        let mut index = Index::with_max_load_factor(Index::NO_RESIZE);
+        for i in 0..100usize {
+            index.insert(&i.to_le_bytes(), handle(i));
+        }
+        assert_eq!(index.bucket_count(), INITIAL_BUCKETS);
+        assert_eq!(index.load_factor(), 100.0 / 16.0);
+        assert!((0..100usize).all(|i| index.get(&i.to_le_bytes()) == Some(handle(i))));
+    }
+}
@@ -3,14 +3,21 @@
 
 //! Core components of the Cortex cache.
 
+#[cfg(feature = "compress")]
+mod compress;
 mod entry;
+mod handle;
+mod hash;
 mod index;
 mod shard;
-mod handle;
+mod shard_set;
 mod slab;
 
+#[cfg(feature = "compress")]
+pub use compress::CompressFilter;
 pub use entry::Entry;
+pub use handle::Handle;
 pub use index::Index;
-pub use shard::Shard;
-pub use slab::Slab;
-pub(crate) use handle::Handle;
+pub use shard::{RestoreStats, Shard};
+pub use shard_set::ShardSet;
+pub use slab::{AllocOutlier, BlockHeader, FailureDiagnostics, FragmentationReport, Slab};
@@ -1,2 +1,660 @@
+use crate::{Handle, Index, Slab};
+use cortex_api::{Cache, PutError, ValueFilter};
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Leading bytes of every snapshot.
+const SNAPSHOT_MAGIC: [u8; 4] = *b"CTXS";
+/// Snapshot format written by [`Shard::snapshot`].
+const SNAPSHOT_VERSION: u16 = 1;
+
+/// Current time in milliseconds since the UNIX epoch, as stored in slab
+/// headers.
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| {
+            u64::try_from(since.as_millis()).unwrap_or(u64::MAX)
+        })
+}
+
+/// Filter shared by every shard of a [`ShardSet`](crate::ShardSet).
+pub(crate) type SharedFilter = Arc<dyn ValueFilter + Send + Sync>;
+
+/// Slab and index guarded together by the shard lock.
+struct Inner {
+    slab: Slab,
+    index: Index,
+    /// Applied to values on their way into and out of the slab.
+    filter: SharedFilter,
+}
+
+impl Inner {
+    /// Logical value of the entry at `handle`, or `None` if it has expired or
+    /// the filter cannot load it.
+    fn load(&self, handle: Handle, now: u64) -> Option<Vec<u8>> {
+        let stored = self.slab.get_value_at(handle, now)?;
+        self.filter.load(stored.into_owned()).ok()
+    }
+
+    /// Copy out the live value under `key`, dropping it if it has expired or
+    /// cannot be loaded.
+    fn get(&mut self, key: &[u8], now: u64) -> Option<Vec<u8>> {
+        let handle = self.index.get(key)?;
+        if let Some(value) = self.load(handle, now) {
+            return Some(value);
+        }
+        self.remove(key);
+        None
+    }
+
+    /// Store `value` under `key`. A failed put leaves any previous value in
+    /// place.
+    fn put(&mut self, key: &[u8], value: &[u8], expires_at: u64) -> Result<(), PutError> {
+        let value = self
+            .filter
+            .store(value.to_vec())
+            .map_err(|_| PutError::Rejected)?;
+        let needed = self.slab.blocks_needed(key.len(), value.len());
+        if key.len() > u16::MAX as usize
+            || value.len() > u16::MAX as usize
+            || needed > self.slab.capacity()
+        {
+            return Err(PutError::TooLarge);
+        }
+        // The old entry's blocks count as free, since a replacement reuses
+        // them.
+        let reclaimed = self
+            .index
+            .get(key)
+            .and_then(|handle| self.slab.header(handle))
+            .map_or(0, |old| {
+                self.slab
+                    .blocks_needed(old.key_len.into(), old.val_len.into())
+            });
+        if needed > self.slab.capacity() - self.slab.len() + reclaimed {
+            return Err(PutError::Full);
+        }
+        // Free the old entry only now that the new one is known to fit.
+        self.remove(key);
+        let handle = self
+            .slab
+            .allocate(key, &value, expires_at)
+            .ok_or(PutError::Full)?;
+        self.index.insert(key, handle);
+        Ok(())
+    }
+
+    /// Remove entries rejected by `keep`, along with any that are no longer
+    /// readable.
+    fn retain(&mut self, now: u64, keep: impl Fn(&[u8], &[u8]) -> bool) {
+        let doomed: Vec<Box<[u8]>> = self
+            .index
+            .iter()
+            .filter(|&(key, handle)| {
+                self.load(handle, now)
+                    .is_none_or(|value| !keep(key, &value))
+            })
+            .map(|(key, _)| key.into())
+            .collect();
+        for key in doomed {
+            self.remove(&key);
+        }
+    }
+
+    fn for_each(&self, now: u64, mut f: impl FnMut(&[u8], &[u8])) {
+        for (key, handle) in self.index.iter() {
+            if let Some(value) = self.load(handle, now) {
+                f(key, &value);
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &[u8]) -> bool {
+        match self.index.remove(key) {
+            Some(handle) => self.slab.deallocate(handle),
+            None => false,
+        }
+    }
+}
+
+/// Outcome of [`Shard::restore`] and [`ShardSet::restore`](crate::ShardSet::restore).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RestoreStats {
+    /// Entries stored in the restored shard.
+    pub restored: usize,
+    /// Entries skipped because they had expired by restore time.
+    pub expired: usize,
+    /// Entries that did not fit in the restored shard.
+    pub rejected: usize,
+}
+
+pub(crate) fn read_array<const N: usize>(r: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    read_array(r).map(u32::from_le_bytes)
+}
+
+fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    read_array(r).map(u64::from_le_bytes)
+}
+
+/// Read a key or value of `len` bytes.
+///
+/// The buffer grows with the bytes actually read rather than being sized
+/// from `len` up front, so a corrupt length cannot exhaust memory.
+fn read_bytes(r: &mut impl Read, len: u32) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    r.by_ref().take(u64::from(len)).read_to_end(&mut buf)?;
+    if buf.len() != len as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(buf)
+}
+
+pub(crate) fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Read one snapshot written by [`Shard::snapshot`], passing every entry
+/// still live at `now` to `put` with its absolute expiry.
+pub(crate) fn read_snapshot(
+    r: &mut impl Read,
+    now: u64,
+    mut put: impl FnMut(&[u8], &[u8], u64) -> Result<(), PutError>,
+) -> io::Result<RestoreStats> {
+    if read_array(r)? != SNAPSHOT_MAGIC {
+        return Err(invalid_data("not a cortex snapshot"));
+    }
+    if u16::from_le_bytes(read_array(r)?) != SNAPSHOT_VERSION {
+        return Err(invalid_data("unsupported snapshot version"));
+    }
+    let taken_at = read_u64(r)?;
+    let count = read_u64(r)?;
+
+    let mut stats = RestoreStats::default();
+    for _ in 0..count {
+        let remaining = read_u64(r)?;
+        let key_len = read_u32(r)?;
+        let val_len = read_u32(r)?;
+        let key = read_bytes(r, key_len)?;
+        let value = read_bytes(r, val_len)?;
+
+        let expires_at = match remaining {
+            0 => 0,
+            remaining => taken_at.saturating_add(remaining),
+        };
+        if Slab::is_expired(expires_at, now) {
+            stats.expired += 1;
+        } else if put(&key, &value, expires_at).is_ok() {
+            stats.restored += 1;
+        } else {
+            stats.rejected += 1;
+        }
+    }
+    Ok(stats)
+}
+
 /// Represents a cache shard.
-pub struct Shard;
+///
+/// A shard owns one [`Slab`] and the [`Index`] of keys stored in it, both
+/// behind a single lock. Values pass through the shard's [`ValueFilter`] on
+/// the way in and out, so callers only ever see their logical form.
+pub struct Shard {
+    inner: Mutex<Inner>,
+}
+
+impl Shard {
+    /// Create a shard backed by a slab of `capacity_bytes`, storing values
+    /// unchanged.
+    pub fn new(capacity_bytes: usize) -> Self {
+        Self::with_filter(capacity_bytes, ())
+    }
+
+    /// Create a shard backed by a slab of `capacity_bytes` that stores values
+    /// through `filter`.
+    pub fn with_filter(
+        capacity_bytes: usize,
+        filter: impl ValueFilter + Send + Sync + 'static,
+    ) -> Self {
+        Self::with_shared_filter(capacity_bytes, Arc::new(filter))
+    }
+
+    pub(crate) fn with_shared_filter(capacity_bytes: usize, filter: SharedFilter) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                slab: Slab::new(capacity_bytes),
+                index: Index::new(),
+                filter,
+            }),
+        }
+    }
+
+    /// Number of keys stored in the shard.
+    pub fn len(&self) -> usize {
+        self.lock().index.len()
+    }
+
+    /// Whether the shard holds no keys.
+    pub fn is_empty(&self) -> bool {
+        self.lock().index.is_empty()
+    }
+
+    /// Largest `key.len() + value.len()` that fits in a single slab block;
+    /// see [`Slab::usable_payload_bytes`].
+    ///
+    /// The value is counted as stored, after the shard's filter. Larger
+    /// entries are still accepted but chained across blocks.
+    pub fn usable_payload_bytes(&self) -> usize {
+        self.lock().slab.usable_payload_bytes()
+    }
+
+    /// Store `value` under `key`, expiring `ttl` from now.
+    pub fn put_with_ttl(&self, key: &[u8], value: &[u8], ttl: Duration) -> Result<(), PutError> {
+        let ttl_ms = u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX);
+        self.put_expiring(key, value, now_millis().saturating_add(ttl_ms))
+    }
+
+    /// Store `value` under `key`, expiring at `expires_at` milliseconds since
+    /// the UNIX epoch, or never if zero.
+    pub(crate) fn put_expiring(
+        &self,
+        key: &[u8],
+        value: &[u8],
+        expires_at: u64,
+    ) -> Result<(), PutError> {
+        self.lock().put(key, value, expires_at)
+    }
+
+    /// Serialize every live entry to `w`.
+    ///
+    /// The snapshot starts with a magic number and format version, followed
+    /// by the time it was taken and the entry count. Each entry records its
+    /// remaining TTL in milliseconds (zero for none) and its length-prefixed
+    /// key and value. Values are written in their logical form, after the
+    /// shard's filter; entries the filter cannot load are left out. All
+    /// integers are little-endian. The shard stays locked while writing.
+    pub fn snapshot(&self, w: &mut impl Write) -> io::Result<()> {
+        let now = now_millis();
+        let inner = self.lock();
+        // Collected up front: the count precedes the entries, and loading a
+        // value through the filter may be expensive.
+        let live: Vec<_> = inner
+            .index
+            .iter()
+            .filter_map(|(_, handle)| {
+                let (expires_at, key, value) = inner.slab.get_meta_at(handle, now)?;
+                let value = inner.filter.load(value.into_owned()).ok()?;
+                Some((expires_at, key, value))
+            })
+            .collect();
+
+        w.write_all(&SNAPSHOT_MAGIC)?;
+        w.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+        w.write_all(&now.to_le_bytes())?;
+        w.write_all(&(live.len() as u64).to_le_bytes())?;
+        for (expires_at, key, value) in live {
+            let remaining = match expires_at {
+                0 => 0,
+                expires_at => expires_at - now,
+            };
+            w.write_all(&remaining.to_le_bytes())?;
+            w.write_all(&(key.len() as u32).to_le_bytes())?;
+            w.write_all(&(value.len() as u32).to_le_bytes())?;
+            w.write_all(&key)?;
+            w.write_all(&value)?;
+        }
+        Ok(())
+    }
+
+    /// Rebuild a shard of `capacity_bytes` from a snapshot written by
+    /// [`Shard::snapshot`].
+    ///
+    /// Entries that expired between the snapshot and now are skipped, and
+    /// entries the new shard cannot hold are counted rather than failing the
+    /// restore. Returns `InvalidData` if the stream is not a snapshot or has
+    /// an unsupported version.
+    pub fn restore(r: &mut impl Read, capacity_bytes: usize) -> io::Result<(Self, RestoreStats)> {
+        Self::restore_with_filter(r, capacity_bytes, ())
+    }
+
+    /// Like [`Shard::restore`], but the new shard stores values through
+    /// `filter`, as if created with [`Shard::with_filter`].
+    ///
+    /// Snapshots hold logical values, so they can be restored under any
+    /// filter. Entries the filter rejects count as rejected.
+    pub fn restore_with_filter(
+        r: &mut impl Read,
+        capacity_bytes: usize,
+        filter: impl ValueFilter + Send + Sync + 'static,
+    ) -> io::Result<(Self, RestoreStats)> {
+        Self::restore_at(r, capacity_bytes, Arc::new(filter), now_millis())
+    }
+
+    fn restore_at(
+        r: &mut impl Read,
+        capacity_bytes: usize,
+        filter: SharedFilter,
+        now: u64,
+    ) -> io::Result<(Self, RestoreStats)> {
+        let shard = Self::with_shared_filter(capacity_bytes, filter);
+        let mut inner = shard.lock();
+        let stats = read_snapshot(r, now, |key, value, expires_at| {
+            inner.put(key, value, expires_at)
+        });
+        drop(inner);
+        Ok((shard, stats?))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        // Nothing is mutated while user code runs under the lock, so the
+        // state is consistent even if that code panicked.
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Cache for Shard {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.lock().get(key, now_millis())
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<(), PutError> {
+        self.lock().put(key, value, 0)
+    }
+
+    fn remove(&self, key: &[u8]) -> bool {
+        self.lock().remove(key)
+    }
+
+    fn retain<F>(&self, keep: F)
+    where
+        F: Fn(&[u8], &[u8]) -> bool,
+    {
+        self.lock().retain(now_millis(), keep);
+    }
+
+    fn for_each<F>(&self, f: F)
+    where
+        F: FnMut(&[u8], &[u8]),
+    {
+        self.lock().for_each(now_millis(), f);
+    }
+
+    fn get_many<K: AsRef<[u8]>>(&self, keys: &[K]) -> Vec<Option<Vec<u8>>> {
+        let now = now_millis();
+        let mut inner = self.lock();
+        keys.iter()
+            .map(|key| inner.get(key.as_ref(), now))
+            .collect()
+    }
+
+    fn put_many<K, V, I>(&self, items: I) -> Vec<Result<(), PutError>>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut inner = self.lock();
+        items
+            .into_iter()
+            .map(|(key, value)| inner.put(key.as_ref(), value.as_ref(), 0))
+            .collect()
+    }
+
+    /// Runs `f` while holding the shard lock, so `f` must not access the
+    /// same shard.
+    fn try_get_or_insert_with<F, E>(&self, key: &[u8], f: F) -> Result<Vec<u8>, E>
+    where
+        F: FnOnce() -> Result<Vec<u8>, E>,
+    {
+        let mut inner = self.lock();
+        if let Some(value) = inner.get(key, now_millis()) {
+            return Ok(value);
+        }
+        let value = f()?;
+        // A value that cannot be cached is still handed back to the caller.
+        let _ = inner.put(key, &value, 0);
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cortex_api::FilterError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Barrier};
+    use std::time::Duration;
+
+    #[test]
+    fn racing_threads_compute_cold_key_once() {
+        const THREADS: usize = 16;
+        let shard = Arc::new(Shard::new(64 * 1024));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(THREADS));
+
+        let workers: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let (shard, calls, barrier) = (shard.clone(), calls.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    shard.get_or_insert_with(b"cold", || {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        std::thread::sleep(Duration::from_millis(10));
+                        b"computed".to_vec()
+                    })
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            assert_eq!(worker.join().expect("worker"), b"computed");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(shard.get(b"cold").as_deref(), Some(&b"computed"[..]));
+    }
+
+    #[test]
+    fn closure_skipped_on_hit_and_value_kept_when_full() {
+        let shard = Shard::new(512);
+        shard.put(b"k", b"v").expect("put");
+        let hit = shard.get_or_insert_with(b"k", || unreachable!("closure ran on a hit"));
+        assert_eq!(hit, b"v");
+
+        // The only block is taken, so the computed value cannot be stored.
+        let big = vec![7u8; 400];
+        assert_eq!(shard.get_or_insert_with(b"other", || big.clone()), big);
+        assert_eq!(shard.get(b"other"), None);
+
+        let failed: Result<_, &str> = shard.try_get_or_insert_with(b"other", || Err("boom"));
+        assert_eq!(failed, Err("boom"));
+        assert!(shard.remove(b"k"));
+        assert!(shard.is_empty());
+    }
+
+    #[test]
+    fn oversized_put_keeps_previous_value() {
+        let shard = Shard::new(64 * 1024);
+        shard.put(b"k", b"v").expect("put");
+        let huge = vec![0u8; u16::MAX as usize + 1];
+        assert_eq!(shard.usable_payload_bytes(), 512 - 27);
+        assert_eq!(shard.put(b"k", &huge), Err(PutError::TooLarge));
+        assert_eq!(shard.get(b"k").as_deref(), Some(&b"v"[..]));
+
+        // Two blocks can never hold a chain of eight, so this is not `Full`.
+        let small = Shard::new(2 * 512);
+        assert_eq!(small.put(b"k", &[0; 4000]), Err(PutError::TooLarge));
+        small.put(b"k", &[0; 600]).expect("two-block entry");
+    }
+
+    #[test]
+    fn put_without_room_keeps_previous_value() {
+        let shard = Shard::new(2 * 512);
+        shard.put(b"a", b"old").expect("put");
+        shard.put(b"b", b"other").expect("put");
+
+        // Two blocks are needed but only the one `a` holds would be freed.
+        assert_eq!(shard.put(b"a", &[1; 600]), Err(PutError::Full));
+        assert_eq!(shard.get(b"a").as_deref(), Some(&b"old"[..]));
+        shard
+            .put(b"a", b"new")
+            .expect("replacement reuses the block");
+
+        assert!(shard.remove(b"b"));
+        shard.put(b"a", &[1; 600]).expect("put");
+        assert_eq!(shard.get(b"a"), Some(vec![1; 600]));
+    }
+
+    #[test]
+    fn snapshot_round_trips_live_entries() {
+        let shard = Shard::new(64 * 1024);
+        shard.put(b"plain", b"value").expect("put");
+        shard.put(b"chained", &[9u8; 1_500]).expect("put");
+        shard
+            .put_with_ttl(b"short", b"gone soon", Duration::from_secs(60))
+            .expect("put");
+        shard
+            .put_with_ttl(b"long", b"stays", Duration::from_secs(3_600))
+            .expect("put");
+
+        let mut bytes = Vec::new();
+        shard.snapshot(&mut bytes).expect("snapshot");
+        assert_eq!(&bytes[..4], b"CTXS");
+
+        let (restored, stats) = Shard::restore(&mut &bytes[..], 64 * 1024).expect("restore");
+        assert_eq!(stats.restored, 4);
+        assert_eq!(restored.len(), 4);
+        for key in [&b"plain"[..], b"chained", b"short", b"long"] {
+            assert_eq!(restored.get(key), shard.get(key));
+        }
+
+        // Ten minutes later only the hour-long entry has TTL left.
+        let later = now_millis() + 600_000;
+        let (restored, stats) =
+            Shard::restore_at(&mut &bytes[..], 64 * 1024, Arc::new(()), later).expect("restore");
+        assert_eq!((stats.restored, stats.expired), (3, 1));
+        assert_eq!(restored.get(b"short"), None);
+
+        // The chained entry needs four blocks; the others get one each.
+        let (small, stats) = Shard::restore(&mut &bytes[..], 3 * 512).expect("restore");
+        assert_eq!(stats.rejected, 1);
+        assert_eq!(small.get(b"chained"), None);
+        assert_eq!(small.get(b"plain").as_deref(), Some(&b"value"[..]));
+    }
+
+    #[test]
+    fn restore_rejects_foreign_and_truncated_streams() {
+        let err = Shard::restore(&mut &b"NOPE\x01\x00"[..], 512)
+            .err()
+            .expect("bad magic");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut bytes = Vec::new();
+        let shard = Shard::new(512);
+        shard.put(b"k", b"v").expect("put");
+        shard.snapshot(&mut bytes).expect("snapshot");
+        bytes[4] = 2;
+        let err = Shard::restore(&mut &bytes[..], 512)
+            .err()
+            .expect("bad version");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        bytes[4] = 1;
+        // Value length field of the only entry, after the 22-byte header,
+        // the TTL and the key length.
+        let val_len = 22 + 8 + 4;
+        let mut huge = bytes.clone();
+        assert_eq!(huge[val_len..val_len + 4], 1u32.to_le_bytes());
+        huge[val_len..val_len + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let err = Shard::restore(&mut &huge[..], 512)
+            .err()
+            .expect("oversized value");
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        bytes.pop();
+        let err = Shard::restore(&mut &bytes[..], 512)
+            .err()
+            .expect("truncated");
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    /// Stores values bitwise inverted; rejects empty values.
+    struct Invert;
+
+    impl ValueFilter for Invert {
+        fn store(&self, value: Vec<u8>) -> Result<Vec<u8>, FilterError> {
+            if value.is_empty() {
+                return Err(FilterError::new("empty value"));
+            }
+            Ok(value.into_iter().map(|b| !b).collect())
+        }
+
+        fn load(&self, stored: Vec<u8>) -> Result<Vec<u8>, FilterError> {
+            Ok(stored.into_iter().map(|b| !b).collect())
+        }
+    }
+
+    #[test]
+    fn restore_with_filter_stores_through_filter() {
+        let shard = Shard::new(64 * 1024);
+        shard.put(b"k", b"value").expect("put");
+        shard.put(b"empty", b"").expect("put");
+        let mut bytes = Vec::new();
+        shard.snapshot(&mut bytes).expect("snapshot");
+
+        let (restored, stats) =
+            Shard::restore_with_filter(&mut &bytes[..], 64 * 1024, Invert).expect("restore");
+        assert_eq!((stats.restored, stats.rejected), (1, 1));
+        assert_eq!(restored.get(b"k").as_deref(), Some(&b"value"[..]));
+        let inner = restored.lock();
+        let handle = inner.index.get(b"k").expect("indexed");
+        assert_eq!(
+            inner.slab.get_value(handle).as_deref(),
+            Some(&b"value".map(|b| !b)[..])
+        );
+    }
+
+    #[test]
+    fn retain_sees_filtered_values_and_drops_expired() {
+        let shard = Shard::with_filter(64 * 1024, Invert);
+        for i in 0..10u8 {
+            shard.put(&[i], &[i; 3]).expect("put");
+        }
+        shard
+            .put_with_ttl(b"stale", &[0], Duration::ZERO)
+            .expect("put");
+        assert_eq!(shard.put(b"empty", b""), Err(PutError::Rejected));
+        shard.put(b"kept", b"old").expect("put");
+        assert_eq!(shard.put(b"kept", b""), Err(PutError::Rejected));
+        assert_eq!(shard.get(b"kept").as_deref(), Some(&b"old"[..]));
+        assert!(shard.remove(b"kept"));
+
+        // The slab holds the stored form, callers see the logical one.
+        let inner = shard.lock();
+        let handle = inner.index.get(&[4]).expect("indexed");
+        assert_eq!(
+            inner.slab.get_value(handle).as_deref(),
+            Some(&[!4u8; 3][..])
+        );
+        drop(inner);
+        assert_eq!(shard.get(&[4]), Some(vec![4; 3]));
+
+        let seen = AtomicUsize::new(0);
+        shard.retain(|key, value| {
+            assert_ne!(key, b"stale");
+            assert_eq!(value, [key[0]; 3]);
+            seen.fetch_add(1, Ordering::Relaxed);
+            key[0] % 3 != 0
+        });
+        assert_eq!(seen.into_inner(), 10);
+        assert_eq!(shard.len(), 6);
+        for i in 0..10u8 {
+            assert_eq!(shard.get(&[i]).is_some(), i % 3 != 0, "key {i}");
+        }
+    }
+}
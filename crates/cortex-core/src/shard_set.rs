@@ -0,0 +1,409 @@
+use crate::hash::fx_hash;
+use crate::shard::{SharedFilter, invalid_data, now_millis, read_array, read_snapshot};
+use crate::{RestoreStats, Shard};
+use cortex_api::{Cache, PutError, ValueFilter};
+use cortex_metrics::Tracer;
+use cortex_metrics::trace::attr;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Leading bytes of every snapshot written by [`ShardSet::snapshot`].
+const SNAPSHOT_MAGIC: [u8; 4] = *b"CTXM";
+
+/// A cache spread over several independently locked [`Shard`]s.
+///
+/// Every key belongs to exactly one shard, picked by [`ShardSet::shard_for`],
+/// so each operation takes a single shard lock and operations on keys in
+/// different shards do not contend.
+///
+/// While spans are exported, `get`, `put` and `remove` each run inside a
+/// [`Tracer::cache_span`] recording the key length, the owning shard and, for
+/// lookups, whether the key was found. `get_many` and `put_many` run one
+/// [`Tracer::cache_batch_span`] per shard they touch, recording the shard,
+/// the number of its keys in the batch and, for lookups, how many were found.
+pub struct ShardSet {
+    shards: Box<[Shard]>,
+    /// log2 of the shard count.
+    shard_bits: u32,
+}
+
+impl ShardSet {
+    /// Create a set of `shards` shards sharing `capacity_bytes` evenly.
+    ///
+    /// The shard count is rounded up to a power of two, and to at least one.
+    pub fn new(shards: usize, capacity_bytes: usize) -> Self {
+        Self::with_filter(shards, capacity_bytes, ())
+    }
+
+    /// Like [`ShardSet::new`], but every shard stores values through
+    /// `filter`.
+    pub fn with_filter(
+        shards: usize,
+        capacity_bytes: usize,
+        filter: impl ValueFilter + Send + Sync + 'static,
+    ) -> Self {
+        let count = shards.max(1).next_power_of_two();
+        let filter: SharedFilter = Arc::new(filter);
+        Self {
+            shards: (0..count)
+                .map(|_| Shard::with_shared_filter(capacity_bytes / count, filter.clone()))
+                .collect(),
+            shard_bits: count.trailing_zeros(),
+        }
+    }
+
+    /// Number of shards, always a power of two.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Index of the shard that owns `key`.
+    ///
+    /// Uses the top bits of the key's FxHash, so the result is stable across
+    /// calls, runs and platforms.
+    pub fn shard_for(&self, key: &[u8]) -> usize {
+        fx_hash(key)
+            .checked_shr(u64::BITS - self.shard_bits)
+            .unwrap_or(0) as usize
+    }
+
+    /// The shard at `index`, as returned by [`ShardSet::shard_for`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not below [`ShardSet::shard_count`].
+    pub(crate) fn shard(&self, index: usize) -> &Shard {
+        &self.shards[index]
+    }
+
+    /// Total number of keys across all shards.
+    ///
+    /// Shards are counted one at a time, so concurrent writes may make the
+    /// total slightly stale.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(Shard::len).sum()
+    }
+
+    /// Whether no shard holds any key.
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(Shard::is_empty)
+    }
+
+    /// Largest `key.len() + value.len()` that fits in a single block of any
+    /// shard; see [`Shard::usable_payload_bytes`].
+    pub fn usable_payload_bytes(&self) -> usize {
+        self.shards[0].usable_payload_bytes()
+    }
+
+    /// Store `value` under `key`, expiring `ttl` from now.
+    pub fn put_with_ttl(&self, key: &[u8], value: &[u8], ttl: Duration) -> Result<(), PutError> {
+        let shard = self.shard_for(key);
+        let _span = Tracer::cache_span("put", key.len(), shard);
+        self.shards[shard].put_with_ttl(key, value, ttl)
+    }
+
+    /// Serialize every live entry to `w`, one shard at a time.
+    ///
+    /// The snapshot starts with a magic number and the shard count as a
+    /// little-endian `u32`, followed by one [`Shard::snapshot`] per shard.
+    /// Only the shard being written is locked, so writes to other shards may
+    /// land while the snapshot is taken.
+    pub fn snapshot(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(&SNAPSHOT_MAGIC)?;
+        w.write_all(&(self.shards.len() as u32).to_le_bytes())?;
+        for shard in self.shards.iter() {
+            shard.snapshot(w)?;
+        }
+        Ok(())
+    }
+
+    /// Rebuild a set of `shards` shards sharing `capacity_bytes` from a
+    /// snapshot written by [`ShardSet::snapshot`].
+    ///
+    /// Entries are routed by key, so the shard count need not match the
+    /// snapshotted set. Expired and unstorable entries are counted as for
+    /// [`Shard::restore`].
+    pub fn restore(
+        r: &mut impl Read,
+        shards: usize,
+        capacity_bytes: usize,
+    ) -> io::Result<(Self, RestoreStats)> {
+        Self::restore_with_filter(r, shards, capacity_bytes, ())
+    }
+
+    /// Like [`ShardSet::restore`], but every shard stores values through
+    /// `filter`, as if created with [`ShardSet::with_filter`].
+    pub fn restore_with_filter(
+        r: &mut impl Read,
+        shards: usize,
+        capacity_bytes: usize,
+        filter: impl ValueFilter + Send + Sync + 'static,
+    ) -> io::Result<(Self, RestoreStats)> {
+        if read_array(r)? != SNAPSHOT_MAGIC {
+            return Err(invalid_data("not a cortex shard set snapshot"));
+        }
+        let count = u32::from_le_bytes(read_array(r)?);
+
+        let set = Self::with_filter(shards, capacity_bytes, filter);
+        let now = now_millis();
+        let mut stats = RestoreStats::default();
+        for _ in 0..count {
+            let shard = read_snapshot(r, now, |key, value, expires_at| {
+                set.owner(key).put_expiring(key, value, expires_at)
+            })?;
+            stats.restored += shard.restored;
+            stats.expired += shard.expired;
+            stats.rejected += shard.rejected;
+        }
+        Ok((set, stats))
+    }
+
+    fn owner(&self, key: &[u8]) -> &Shard {
+        self.shard(self.shard_for(key))
+    }
+
+    /// Positions of `keys` grouped by owning shard, in input order within
+    /// each group.
+    fn group_by_shard<'k>(
+        &self,
+        keys: impl IntoIterator<Item = &'k [u8]>,
+    ) -> Vec<(usize, Vec<usize>)> {
+        let mut groups = vec![Vec::new(); self.shards.len()];
+        for (position, key) in keys.into_iter().enumerate() {
+            groups[self.shard_for(key)].push(position);
+        }
+        groups
+            .into_iter()
+            .enumerate()
+            .filter(|(_, positions)| !positions.is_empty())
+            .collect()
+    }
+}
+
+impl Cache for ShardSet {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let shard = self.shard_for(key);
+        let mut span = Tracer::cache_span("get", key.len(), shard);
+        let value = self.shards[shard].get(key);
+        if let Some(span) = &mut span {
+            span.set_attribute(attr::HIT, value.is_some());
+        }
+        value
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<(), PutError> {
+        let shard = self.shard_for(key);
+        let _span = Tracer::cache_span("put", key.len(), shard);
+        self.shards[shard].put(key, value)
+    }
+
+    fn remove(&self, key: &[u8]) -> bool {
+        let shard = self.shard_for(key);
+        let mut span = Tracer::cache_span("remove", key.len(), shard);
+        let removed = self.shards[shard].remove(key);
+        if let Some(span) = &mut span {
+            span.set_attribute(attr::HIT, removed);
+        }
+        removed
+    }
+
+    /// Visits the shards one at a time, holding only that shard's lock.
+    fn retain<F>(&self, keep: F)
+    where
+        F: Fn(&[u8], &[u8]) -> bool,
+    {
+        for shard in self.shards.iter() {
+            shard.retain(&keep);
+        }
+    }
+
+    /// Visits the shards one at a time, holding only that shard's lock.
+    fn for_each<F>(&self, mut f: F)
+    where
+        F: FnMut(&[u8], &[u8]),
+    {
+        for shard in self.shards.iter() {
+            shard.for_each(&mut f);
+        }
+    }
+
+    /// Takes each involved shard lock once, via [`Shard::get_many`].
+    fn get_many<K: AsRef<[u8]>>(&self, keys: &[K]) -> Vec<Option<Vec<u8>>> {
+        let mut results = vec![None; keys.len()];
+        for (shard, positions) in self.group_by_shard(keys.iter().map(AsRef::as_ref)) {
+            let mut span = Tracer::cache_batch_span("get_many", positions.len(), shard);
+            let batch: Vec<&[u8]> = positions.iter().map(|&i| keys[i].as_ref()).collect();
+            let values = self.shards[shard].get_many(&batch);
+            if let Some(span) = &mut span {
+                span.set_attribute(attr::HITS, values.iter().flatten().count());
+            }
+            for (i, value) in positions.into_iter().zip(values) {
+                results[i] = value;
+            }
+        }
+        results
+    }
+
+    /// Takes each involved shard lock once, via [`Shard::put_many`].
+    fn put_many<K, V, I>(&self, items: I) -> Vec<Result<(), PutError>>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let items: Vec<(K, V)> = items.into_iter().collect();
+        let mut results = vec![Ok(()); items.len()];
+        for (shard, positions) in self.group_by_shard(items.iter().map(|(key, _)| key.as_ref())) {
+            let _span = Tracer::cache_batch_span("put_many", positions.len(), shard);
+            let batch = positions
+                .iter()
+                .map(|&i| (items[i].0.as_ref(), items[i].1.as_ref()));
+            for (i, result) in positions.iter().zip(self.shards[shard].put_many(batch)) {
+                results[*i] = result;
+            }
+        }
+        results
+    }
+
+    fn try_get_or_insert_with<F, E>(&self, key: &[u8], f: F) -> Result<Vec<u8>, E>
+    where
+        F: FnOnce() -> Result<Vec<u8>, E>,
+    {
+        self.owner(key).try_get_or_insert_with(key, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn keys_spread_evenly_across_shards() {
+        let set = ShardSet::new(16, 16 * 512);
+        let mut counts = [0usize; 16];
+        for i in 0..10_000 {
+            counts[set.shard_for(format!("key-{i}").as_bytes())] += 1;
+        }
+        let mean = 10_000 / 16;
+        assert!(
+            counts.iter().all(|&count| count <= 2 * mean),
+            "uneven distribution: {counts:?}"
+        );
+    }
+
+    #[test]
+    fn key_length_is_part_of_the_hash() {
+        assert_ne!(fx_hash(b"a"), fx_hash(b"a\0"));
+        assert_ne!(fx_hash(&0u32.to_le_bytes()), fx_hash(&0u64.to_le_bytes()));
+
+        let set = ShardSet::new(16, 16 * 4096);
+        let shards: HashSet<usize> = (0..64).map(|len| set.shard_for(&vec![0; len])).collect();
+        assert!(shards.len() > 8, "zero keys share shards: {shards:?}");
+    }
+
+    #[test]
+    fn snapshot_restores_into_a_different_shard_count() {
+        let set = ShardSet::new(4, 4 * 4096);
+        for i in 0..20u32 {
+            set.put(&i.to_le_bytes(), &[i as u8; 10]).expect("put");
+        }
+        set.put_with_ttl(b"ttl", b"later", Duration::from_secs(3_600))
+            .expect("put");
+        // Already expired, so left out of the snapshot.
+        set.put_with_ttl(b"gone", b"now", Duration::ZERO)
+            .expect("put");
+        let mut bytes = Vec::new();
+        set.snapshot(&mut bytes).expect("snapshot");
+
+        let (restored, stats) = ShardSet::restore(&mut &bytes[..], 16, 16 * 4096).expect("restore");
+        assert_eq!((stats.restored, stats.expired, stats.rejected), (21, 0, 0));
+        assert_eq!(restored.shard_count(), 16);
+        for i in 0..20u32 {
+            assert_eq!(restored.get(&i.to_le_bytes()), Some(vec![i as u8; 10]));
+        }
+        assert_eq!(restored.get(b"ttl").as_deref(), Some(&b"later"[..]));
+        assert_eq!(restored.get(b"gone"), None);
+
+        // A plain shard snapshot is not a set snapshot.
+        let mut shard_bytes = Vec::new();
+        set.shard(0).snapshot(&mut shard_bytes).expect("snapshot");
+        let err = ShardSet::restore(&mut &shard_bytes[..], 4, 4 * 4096)
+            .err()
+            .expect("foreign stream");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn shard_count_rounds_up_and_routing_is_stable() {
+        assert_eq!(ShardSet::new(0, 0).shard_count(), 1);
+        assert_eq!(ShardSet::new(1, 0).shard_for(b"anything"), 0);
+        let set = ShardSet::new(5, 8 * 4096);
+        assert_eq!(set.shard_count(), 8);
+        assert_eq!(
+            set.usable_payload_bytes(),
+            set.shard(7).usable_payload_bytes()
+        );
+        assert_eq!(set.shard_for(b"user:42"), set.shard_for(b"user:42"));
+
+        set.put(b"user:42", b"alice").expect("put");
+        let owner = set.shard(set.shard_for(b"user:42"));
+        assert_eq!(owner.get(b"user:42").as_deref(), Some(&b"alice"[..]));
+        assert_eq!(set.len(), 1);
+        assert!(set.remove(b"user:42"));
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn for_each_visits_every_key_once_and_retain_filters_across_shards() {
+        let set = ShardSet::new(8, 8 * 64 * 1024);
+        for i in 0..500u32 {
+            set.put(&i.to_le_bytes(), &(i * 2).to_le_bytes())
+                .expect("put");
+        }
+        let mut visits = vec![0; 500];
+        set.for_each(|key, value| {
+            let key = u32::from_le_bytes(key.try_into().expect("4-byte key"));
+            assert_eq!(value, (key * 2).to_le_bytes());
+            visits[key as usize] += 1;
+        });
+        assert!(visits.iter().all(|&count| count == 1));
+        assert!((0..8).all(|shard| !set.shard(shard).is_empty()));
+
+        set.retain(|key, _| u32::from_le_bytes(key.try_into().expect("key")) % 5 == 0);
+        assert_eq!(set.len(), 100);
+        let mut kept = Vec::new();
+        set.for_each(|key, _| kept.push(u32::from_le_bytes(key.try_into().expect("key"))));
+        kept.sort_unstable();
+        assert_eq!(kept, (0..500).filter(|i| i % 5 == 0).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn batches_keep_input_order_and_isolate_failures() {
+        let set = ShardSet::new(4, 4 * 64 * 1024);
+        let oversized = vec![0u8; usize::from(u16::MAX) + 1];
+        let keys: Vec<String> = (0..40).map(|i| format!("key-{i}")).collect();
+        let mut items: Vec<(&[u8], &[u8])> = keys
+            .iter()
+            .step_by(2)
+            .map(|key| (key.as_bytes(), key.as_bytes()))
+            .collect();
+        items.insert(5, (b"huge", &oversized));
+
+        let results = set.put_many(items);
+        assert_eq!(results.len(), 21);
+        assert_eq!(results[5], Err(PutError::TooLarge));
+        assert!(results.iter().enumerate().all(|(i, r)| i == 5 || r.is_ok()));
+        assert_eq!(set.len(), 20);
+
+        let mut lookups: Vec<&[u8]> = keys.iter().map(String::as_bytes).collect();
+        lookups.push(b"huge");
+        let values = set.get_many(&lookups);
+        assert_eq!(values.len(), 41);
+        for (i, value) in values.iter().enumerate() {
+            let expected = (i % 2 == 0 && i < 40).then(|| lookups[i].to_vec());
+            assert_eq!(*value, expected, "key {i}");
+        }
+    }
+}
@@ -4,23 +4,74 @@
 //! blocks. Each block stores metadata and the key/value payload:
 //!
 //! ```text
-//! [ TTL (8 bytes) ][ KeyLen (2 bytes) ][ ValLen (2 bytes) ][ Key ][ Value ]
+//! [ TTL (8 bytes) ][ KeyLen (2 bytes) ][ ValLen (2 bytes) ][ Seq (8 bytes) ][ Next (4 bytes) ][ Gen (2 bytes) ][ Flags (1 byte) ][ Key ][ Value ]
 //! ```
 //!
+//! Entries too large for a single block are spread over a chain of blocks.
+//! `Next` holds the index of the following block plus one, or zero at the end
+//! of the chain. The head block carries the full header; continuation blocks
+//! are marked in `Flags` and only their `Next`, `Gen` and `Flags` fields are
+//! meaningful. The key and value are written as one
+//! stream across the payload area after each block's header, so an entry that
+//! fits in one block is laid out exactly as before.
+//!
+//! `TTL` holds the absolute expiry instant of the entry in milliseconds since
+//! the UNIX epoch, never a relative duration, so every consumer can compare it
+//! against the current time directly. [`Slab::allocate_with_ttl`] converts a
+//! relative TTL into this form at insert time. A `TTL` of zero means the entry
+//! never expires. An entry is expired at `now` once `TTL <= now`; the `_at`
+//! accessors and [`Slab::purge_expired`] apply that rule, while the plain
+//! accessors ignore expiry for callers that track it elsewhere.
+//!
+//! `Seq` is the slab-wide insert sequence number the block was allocated at,
+//! giving a total order of inserts independent of the wall clock.
+//!
+//! `Gen` is bumped every time the block is freed and survives the zeroing
+//! that follows. Handles record the generation they were issued at, so a
+//! stale handle to a block that has since been reused reads as `None` instead
+//! of returning the new occupant's data.
+//!
+//! Blocks are 512 bytes unless the slab is created with
+//! [`Slab::with_block_size`] or [`Slab::with_layout`]; only the block span
+//! changes, the header layout is the same for every size.
+//!
+//! A slab created with [`Slab::with_value_alignment`] pads after the key so
+//! that the value starts at a multiple of the alignment from the block start.
+//! The region is over-allocated so that block 0, and with it every block,
+//! starts at an aligned address.
+//!
 //! All offsets are encoded manually using byte operations to keep the
 //! allocator lock-free and allocation-free after initialisation.
 
 use crate::Handle;
-use core::convert::TryInto;
+use core::time::Duration;
+use std::borrow::Cow;
+use std::time::Instant;
 
 /// Memory slab allocator using a simple freelist.
 pub struct Slab {
     /// Contiguous region backing the slab.
     region: Box<[u8]>,
+    /// Offset of block 0 within `region`, chosen so that every block starts
+    /// at an address aligned to `value_align`.
+    base: usize,
     /// Total number of blocks in the region.
     total_blocks: usize,
     /// Indices of currently free blocks.
     free_list: Vec<usize>,
+    /// One bit per block, set while the block is on the freelist.
+    free_bits: Vec<u64>,
+    /// Sequence number assigned to the next allocation.
+    next_seq: u64,
+    /// Header, key and value bytes of live entries, excluding padding.
+    bytes_used: usize,
+    /// Size of every block in bytes, header included.
+    block_size: usize,
+    /// Alignment of the value payload, both relative to the block start and
+    /// in memory.
+    value_align: usize,
+    /// Allocation latency tracking, if enabled.
+    outliers: Option<OutlierTracker>,
 }
 
 // Layout constants ---------------------------------------------------------
@@ -28,9 +79,129 @@ const TTL_OFFSET: usize = 0; // start of TTL field
 const TTL_SIZE: usize = 8; // u64
 const KEY_LEN_OFFSET: usize = TTL_OFFSET + TTL_SIZE; // 8
 const VAL_LEN_OFFSET: usize = KEY_LEN_OFFSET + 2; // 10
-const HEADER_SIZE: usize = VAL_LEN_OFFSET + 2; // 12
-/// Fixed block size used by this allocator.
-pub(crate) const BLOCK_SIZE: usize = 512; // bytes
+const SEQ_OFFSET: usize = VAL_LEN_OFFSET + 2; // 12
+const SEQ_SIZE: usize = 8; // u64
+const NEXT_OFFSET: usize = SEQ_OFFSET + SEQ_SIZE; // 20
+const NEXT_SIZE: usize = 4; // u32, next block index + 1
+const GEN_OFFSET: usize = NEXT_OFFSET + NEXT_SIZE; // 24
+const GEN_SIZE: usize = 2; // u16
+const FLAGS_OFFSET: usize = GEN_OFFSET + GEN_SIZE; // 26
+const FLAGS_SIZE: usize = 1; // u8
+const HEADER_SIZE: usize = FLAGS_OFFSET + FLAGS_SIZE; // 27
+/// Block size used by [`Slab::new`] and [`Slab::with_value_alignment`].
+pub(crate) const DEFAULT_BLOCK_SIZE: usize = 512; // bytes
+/// Flag marking a block that continues another block's entry.
+const FLAG_CONTINUATION: u8 = 0x01;
+
+/// Decoded header fields of a block, as returned by [`Slab::header`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockHeader {
+    /// Absolute expiry in milliseconds since the UNIX epoch.
+    pub expires_at: u64,
+    /// Length of the key in bytes.
+    pub key_len: u16,
+    /// Length of the value in bytes.
+    pub val_len: u16,
+    /// Insert sequence number the block was allocated at.
+    pub sequence: u64,
+    /// Whether the entry continues into further blocks.
+    pub chained: bool,
+    /// Number of times the block has been freed.
+    pub generation: u16,
+}
+
+impl BlockHeader {
+    /// Decode the header at the start of `block`.
+    fn decode(block: &[u8]) -> Self {
+        let u16_at = |at: usize| u16::from_le_bytes([block[at], block[at + 1]]);
+        let u64_at = |at: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&block[at..at + 8]);
+            u64::from_le_bytes(bytes)
+        };
+        Self {
+            expires_at: u64_at(TTL_OFFSET),
+            key_len: u16_at(KEY_LEN_OFFSET),
+            val_len: u16_at(VAL_LEN_OFFSET),
+            sequence: u64_at(SEQ_OFFSET),
+            chained: block[NEXT_OFFSET..NEXT_OFFSET + NEXT_SIZE] != [0; NEXT_SIZE],
+            generation: u16_at(GEN_OFFSET),
+        }
+    }
+}
+
+/// A slow allocation recorded by [`Slab::track_alloc_outliers`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllocOutlier {
+    /// Time the allocation took.
+    pub latency: Duration,
+    /// Key plus value bytes requested.
+    pub requested_bytes: usize,
+    /// Whether a block was handed out.
+    pub succeeded: bool,
+}
+
+/// Expiry, key and value of an entry as returned by [`Slab::get_meta`].
+type Meta<'a> = (u64, Cow<'a, [u8]>, Cow<'a, [u8]>);
+
+/// Monotonic time source used to measure allocation latency.
+type AllocTimer = Box<dyn Fn() -> Duration + Send + Sync>;
+
+/// Ring buffer of recent allocations slower than a threshold.
+struct OutlierTracker {
+    threshold: Duration,
+    timer: AllocTimer,
+    /// Preallocated so that recording never allocates.
+    recent: Vec<AllocOutlier>,
+    capacity: usize,
+    /// Slot the next outlier overwrites once `recent` is full.
+    next: usize,
+}
+
+impl OutlierTracker {
+    fn record(&mut self, outlier: AllocOutlier) {
+        if self.recent.len() < self.capacity {
+            self.recent.push(outlier);
+        } else {
+            self.recent[self.next] = outlier;
+        }
+        self.next = (self.next + 1) % self.capacity;
+    }
+}
+
+/// Snapshot of freelist state returned by [`Slab::fragmentation_report`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FragmentationReport {
+    /// Free block counts per size class as `(block_size, free_blocks)`.
+    ///
+    /// The slab currently has a single size class, so this holds one entry.
+    pub free_by_size_class: Vec<(usize, usize)>,
+    /// Length of the longest run of adjacent free blocks.
+    pub largest_free_run: usize,
+}
+
+/// One-shot snapshot of slab state for diagnosing allocation failures,
+/// returned by [`Slab::failure_diagnostics`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FailureDiagnostics {
+    /// Blocks left on the freelist.
+    pub free_blocks: usize,
+    /// Fraction of blocks in use, as reported by [`Slab::load_factor`].
+    pub load_factor: f64,
+    /// Length of the largest value currently stored.
+    pub largest_value: usize,
+    /// Freelist fragmentation at the time of the snapshot.
+    pub fragmentation: FragmentationReport,
+}
+
+/// Initialise a bitmap with the bits for `n` blocks set.
+fn init_free_bits(n: usize) -> Vec<u64> {
+    let mut bits = vec![u64::MAX; n.div_ceil(64)];
+    if let Some(last) = bits.last_mut().filter(|_| !n.is_multiple_of(64)) {
+        *last = (1 << (n % 64)) - 1;
+    }
+    bits
+}
 
 /// Initialise a freelist containing `n` block indices in LIFO order.
 fn init_freelist(n: usize) -> Vec<usize> {
@@ -47,38 +218,370 @@ impl Slab {
     /// Memory is divided into 512-byte blocks; the total capacity is truncated
     /// to a multiple of the block size.
     pub fn new(capacity_bytes: usize) -> Self {
-        let total_blocks = capacity_bytes / BLOCK_SIZE;
-        let region = vec![0u8; total_blocks * BLOCK_SIZE].into_boxed_slice();
+        Self::with_value_alignment(capacity_bytes, 1)
+    }
+
+    /// Create a slab of `block_size`-byte blocks.
+    ///
+    /// Larger blocks hold bigger values without chaining; smaller blocks
+    /// waste less space on tiny entries. The total capacity is truncated to a
+    /// multiple of the block size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block_size` is not a power of two with room for the header
+    /// and at least one payload byte, i.e. smaller than 32, or if the slab
+    /// would hold more blocks than a chain link can address.
+    pub fn with_block_size(capacity_bytes: usize, block_size: usize) -> Self {
+        Self::with_layout(capacity_bytes, block_size, 1)
+    }
+
+    /// Create a slab whose values start at a multiple of `align` bytes from
+    /// the start of their block.
+    ///
+    /// Padding is inserted after the key, so aligned values leave less room
+    /// for payload. Blocks start at aligned addresses, so the values returned
+    /// by [`Slab::get_value`] are aligned in memory too. Chained entries are
+    /// copied out on read, so only single-block values are returned aligned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of two or exceeds the block size, or
+    /// if the slab would hold more blocks than a chain link can address.
+    pub fn with_value_alignment(capacity_bytes: usize, align: usize) -> Self {
+        Self::with_layout(capacity_bytes, DEFAULT_BLOCK_SIZE, align)
+    }
+
+    /// Create a slab of `block_size`-byte blocks whose values start at a
+    /// multiple of `align` bytes, combining [`Slab::with_block_size`] and
+    /// [`Slab::with_value_alignment`].
+    ///
+    /// # Panics
+    ///
+    /// Panics under the conditions of both constructors.
+    pub fn with_layout(capacity_bytes: usize, block_size: usize, align: usize) -> Self {
+        assert!(
+            block_size.is_power_of_two() && block_size > HEADER_SIZE,
+            "block size must be a power of two larger than the {HEADER_SIZE}-byte header"
+        );
+        assert!(
+            align.is_power_of_two() && align <= block_size,
+            "value alignment must be a power of two no larger than the block size"
+        );
+        let total_blocks = capacity_bytes / block_size;
+        assert!(
+            total_blocks < u32::MAX as usize,
+            "too many blocks for chain links"
+        );
+        // Spare bytes let block 0 start at an aligned address; blocks are a
+        // multiple of the alignment apart, so all of them do.
+        let region = vec![0u8; total_blocks * block_size + align - 1].into_boxed_slice();
+        let base = (align - region.as_ptr() as usize % align) % align;
 
         let free_list = init_freelist(total_blocks);
+        let free_bits = init_free_bits(total_blocks);
 
         Self {
             region,
+            base,
             total_blocks,
             free_list,
+            free_bits,
+            next_seq: 0,
+            bytes_used: 0,
+            block_size,
+            value_align: align,
+            outliers: None,
+        }
+    }
+
+    /// Number of blocks currently in use.
+    pub fn len(&self) -> usize {
+        self.total_blocks - self.free_list.len()
+    }
+
+    /// Whether no blocks are in use.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total number of blocks, after truncating the requested capacity to a
+    /// multiple of the block size.
+    pub fn capacity(&self) -> usize {
+        self.total_blocks
+    }
+
+    /// Size of every block in bytes, header included.
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Offset of block `index` within the backing region.
+    fn block_offset(&self, index: usize) -> usize {
+        self.base + index * self.block_size
+    }
+
+    /// Number of blocks available for allocation.
+    pub fn free_blocks(&self) -> usize {
+        self.free_list.len()
+    }
+
+    /// Whether every block is in use.
+    pub fn is_full(&self) -> bool {
+        self.free_list.is_empty()
+    }
+
+    /// Fraction of blocks in use, from 0.0 (empty) to 1.0 (full).
+    ///
+    /// A slab with no blocks reports 1.0 since nothing can be allocated.
+    pub fn load_factor(&self) -> f64 {
+        if self.total_blocks == 0 {
+            return 1.0;
+        }
+        self.len() as f64 / self.total_blocks as f64
+    }
+
+    /// Bytes actually occupied by live entries: one header per block plus the
+    /// key and value bytes.
+    ///
+    /// Compare with `len() * block_size()` to see how much block space is lost to
+    /// partially filled blocks and alignment padding.
+    pub fn bytes_used(&self) -> usize {
+        self.bytes_used
+    }
+
+    /// Payload bytes a single block holds after its header and any value
+    /// alignment padding.
+    ///
+    /// Without alignment this is the largest `key.len() + value.len()` that
+    /// fits in one block; with alignment it is the largest value storable in
+    /// one block under an empty key, and keys may add further padding.
+    /// Anything larger is chained across blocks.
+    pub fn usable_payload_bytes(&self) -> usize {
+        self.block_size - self.value_start(0)
+    }
+
+    /// Number of blocks an entry with a `key_len`-byte key and a
+    /// `value_len`-byte value occupies, counting every block of a chain.
+    ///
+    /// An entry needing more blocks than [`Slab::capacity`] can never be
+    /// stored, however many blocks are freed.
+    pub fn blocks_needed(&self, key_len: usize, value_len: usize) -> usize {
+        let val_start = self.value_start(key_len);
+        if val_start + value_len <= self.block_size {
+            return 1;
         }
+        (val_start - HEADER_SIZE + value_len).div_ceil(self.payload_size())
+    }
+
+    /// Payload bytes after the header of every block, ignoring alignment.
+    fn payload_size(&self) -> usize {
+        self.block_size - HEADER_SIZE
+    }
+
+    /// Offset of the value within a block holding a key of `key_len` bytes.
+    fn value_start(&self, key_len: usize) -> usize {
+        (HEADER_SIZE + key_len).next_multiple_of(self.value_align)
     }
 
-    /// Allocate a block for the provided key/value pair and TTL.
+    /// Allocate a block for the provided key/value pair expiring `ttl` after
+    /// `now`.
     ///
-    /// Returns a [`Handle`] to the allocated block or `None` if the slab is full
-    /// or the data exceeds the block size.
-    pub fn allocate(&mut self, key: &[u8], value: &[u8], ttl: u64) -> Option<Handle> {
-        // Ensure lengths fit in our fixed block.
+    /// `now` is the current time in milliseconds since the UNIX epoch; the
+    /// stored expiry saturates rather than wrapping.
+    pub fn allocate_with_ttl(
+        &mut self,
+        key: &[u8],
+        value: &[u8],
+        ttl: Duration,
+        now: u64,
+    ) -> Option<Handle> {
+        let ttl_ms = u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX);
+        self.allocate(key, value, now.saturating_add(ttl_ms))
+    }
+
+    /// Allocate a block for the provided key/value pair and absolute expiry.
+    ///
+    /// `expires_at` is in milliseconds since the UNIX epoch. Entries that do
+    /// not fit in one block are chained across as many blocks as needed; the
+    /// returned [`Handle`] refers to the head of the chain. Returns `None` if
+    /// there are not enough free blocks or a length exceeds `u16::MAX`.
+    pub fn allocate(&mut self, key: &[u8], value: &[u8], expires_at: u64) -> Option<Handle> {
+        let Some(start) = self.outliers.as_ref().map(|t| (t.timer)()) else {
+            return self.allocate_block(key, value, expires_at);
+        };
+        let handle = self.allocate_block(key, value, expires_at);
+        if let Some(tracker) = self.outliers.as_mut() {
+            let latency = (tracker.timer)().saturating_sub(start);
+            if latency > tracker.threshold {
+                tracker.record(AllocOutlier {
+                    latency,
+                    requested_bytes: key.len() + value.len(),
+                    succeeded: handle.is_some(),
+                });
+            }
+        }
+        handle
+    }
+
+    /// Record allocations slower than `threshold` in a ring buffer holding
+    /// the `capacity` most recent ones, measured with the system monotonic
+    /// clock.
+    ///
+    /// Replaces any previous tracking configuration and clears the buffer.
+    pub fn track_alloc_outliers(&mut self, threshold: Duration, capacity: usize) {
+        let base = Instant::now();
+        self.track_alloc_outliers_with_timer(threshold, capacity, move || base.elapsed());
+    }
+
+    /// Like [`Slab::track_alloc_outliers`] but reads time from `timer`, which
+    /// must be monotonic.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn track_alloc_outliers_with_timer(
+        &mut self,
+        threshold: Duration,
+        capacity: usize,
+        timer: impl Fn() -> Duration + Send + Sync + 'static,
+    ) {
+        assert!(capacity > 0, "outlier buffer needs a non-zero capacity");
+        self.outliers = Some(OutlierTracker {
+            threshold,
+            timer: Box::new(timer),
+            recent: Vec::with_capacity(capacity),
+            capacity,
+            next: 0,
+        });
+    }
+
+    /// Recently recorded slow allocations, oldest first.
+    ///
+    /// Empty unless tracking was enabled with [`Slab::track_alloc_outliers`].
+    pub fn recent_alloc_outliers(&self) -> Vec<AllocOutlier> {
+        let Some(tracker) = &self.outliers else {
+            return Vec::new();
+        };
+        if tracker.recent.len() < tracker.capacity {
+            return tracker.recent.clone();
+        }
+        let (newer, older) = tracker.recent.split_at(tracker.next);
+        older.iter().chain(newer).copied().collect()
+    }
+
+    /// Write the entry into free blocks; the untimed body of `allocate`.
+    fn allocate_block(&mut self, key: &[u8], value: &[u8], expires_at: u64) -> Option<Handle> {
+        // Ensure lengths fit in the header's length fields.
         if key.len() > u16::MAX as usize || value.len() > u16::MAX as usize {
             return None;
         }
-        let required = HEADER_SIZE + key.len() + value.len();
-        if required > BLOCK_SIZE {
+        let val_start = self.value_start(key.len());
+        let required = val_start + value.len();
+        if required > self.block_size {
+            return self.allocate_chain(key, value, expires_at);
+        }
+
+        let index = self.take_free_block()?;
+        self.write_header(index, key, value, expires_at);
+        let offset = self.block_offset(index);
+        let block = &mut self.region[offset..offset + self.block_size];
+
+        // Copy key bytes directly after the header.
+        let key_start = HEADER_SIZE;
+        let key_end = key_start + key.len();
+        block[key_start..key_end].copy_from_slice(key);
+
+        // Copy value bytes after the key, past any alignment padding.
+        let val_end = val_start + value.len();
+        block[val_start..val_end].copy_from_slice(value);
+
+        self.bytes_used += HEADER_SIZE + key.len() + value.len();
+        Some(self.handle_for(index))
+    }
+
+    /// Spread an entry that does not fit in one block across a chain.
+    fn allocate_chain(&mut self, key: &[u8], value: &[u8], expires_at: u64) -> Option<Handle> {
+        let val_offset = self.value_start(key.len()) - HEADER_SIZE;
+        let needed = self.blocks_needed(key.len(), value.len());
+        if needed > self.free_list.len() {
             return None;
         }
 
+        let head = self.take_free_block()?;
+        self.write_header(head, key, value, expires_at);
+        let mut tail = head;
+        for _ in 1..needed {
+            let index = self.take_free_block()?;
+            self.set_next_block(tail, Some(index));
+            self.region[self.block_offset(index) + FLAGS_OFFSET] = FLAG_CONTINUATION;
+            tail = index;
+        }
+
+        self.write_stream(head, 0, key);
+        self.write_stream(head, val_offset, value);
+        self.bytes_used += needed * HEADER_SIZE + key.len() + value.len();
+        Some(self.handle_for(head))
+    }
+
+    /// Pop a block off the freelist and mark it in use.
+    fn take_free_block(&mut self) -> Option<usize> {
         let index = self.free_list.pop()?;
-        let offset = index * BLOCK_SIZE;
-        let block = &mut self.region[offset..offset + BLOCK_SIZE];
+        self.free_bits[index / 64] &= !(1 << (index % 64));
+        Some(index)
+    }
+
+    /// Current generation of block `index`.
+    fn generation(&self, index: usize) -> u16 {
+        let at = self.block_offset(index) + GEN_OFFSET;
+        u16::from_le_bytes([self.region[at], self.region[at + 1]])
+    }
+
+    /// Handle to block `index` at its current generation.
+    fn handle_for(&self, index: usize) -> Handle {
+        Handle {
+            index,
+            generation: self.generation(index),
+        }
+    }
+
+    /// Block index that `handle` refers to, if the block is allocated and has
+    /// not been freed since the handle was issued.
+    fn live_index(&self, handle: Handle) -> Option<usize> {
+        let index = handle.index;
+        if index >= self.total_blocks
+            || self.is_free(index)
+            || self.generation(index) != handle.generation
+        {
+            return None;
+        }
+        Some(index)
+    }
+
+    /// Whether entry expiry `expires_at` has passed at `now`.
+    pub(crate) fn is_expired(expires_at: u64, now: u64) -> bool {
+        expires_at != 0 && expires_at <= now
+    }
+
+    /// Whether block `index` is allocated and starts an entry, rather than
+    /// continuing one.
+    fn is_head(&self, index: usize) -> bool {
+        !self.is_free(index)
+            && self.region[self.block_offset(index) + FLAGS_OFFSET] & FLAG_CONTINUATION == 0
+    }
+
+    /// Whether block `index` is currently on the freelist.
+    fn is_free(&self, index: usize) -> bool {
+        self.free_bits[index / 64] & (1 << (index % 64)) != 0
+    }
 
-        // Encode TTL (u64 LE).
-        block[TTL_OFFSET..TTL_OFFSET + TTL_SIZE].copy_from_slice(&ttl.to_le_bytes());
+    /// Encode the head block header for an entry, stamping a fresh sequence.
+    fn write_header(&mut self, index: usize, key: &[u8], value: &[u8], expires_at: u64) {
+        let offset = self.block_offset(index);
+        let block = &mut self.region[offset..offset + HEADER_SIZE];
+
+        // Encode absolute expiry (u64 LE).
+        block[TTL_OFFSET..TTL_OFFSET + TTL_SIZE].copy_from_slice(&expires_at.to_le_bytes());
 
         // Encode key and value lengths (u16 LE).
         let key_len = key.len() as u16;
@@ -86,110 +589,286 @@ impl Slab {
         block[KEY_LEN_OFFSET..KEY_LEN_OFFSET + 2].copy_from_slice(&key_len.to_le_bytes());
         block[VAL_LEN_OFFSET..VAL_LEN_OFFSET + 2].copy_from_slice(&val_len.to_le_bytes());
 
-        // Copy key bytes directly after the header.
-        let key_start = HEADER_SIZE;
-        let key_end = key_start + key.len();
-        block[key_start..key_end].copy_from_slice(key);
+        // Stamp the insert sequence number (u64 LE).
+        block[SEQ_OFFSET..SEQ_OFFSET + SEQ_SIZE].copy_from_slice(&self.next_seq.to_le_bytes());
+        self.next_seq += 1;
+    }
 
-        // Copy value bytes immediately after key bytes.
-        let val_start = key_end;
-        let val_end = val_start + value.len();
-        block[val_start..val_end].copy_from_slice(value);
+    /// Index of the block chained after `index`, if any.
+    fn next_block(&self, index: usize) -> Option<usize> {
+        let at = self.block_offset(index) + NEXT_OFFSET;
+        let mut bytes = [0u8; NEXT_SIZE];
+        bytes.copy_from_slice(&self.region[at..at + NEXT_SIZE]);
+        // Zero marks the end of the chain; anything else is index + 1.
+        let next = (u32::from_le_bytes(bytes) as usize).checked_sub(1)?;
+        (next < self.total_blocks).then_some(next)
+    }
 
-        Some(Handle(index))
+    /// Link `next` after `index`, or mark `index` as the end of its chain.
+    fn set_next_block(&mut self, index: usize, next: Option<usize>) {
+        let link = next.map_or(0, |n| n as u32 + 1);
+        let at = self.block_offset(index) + NEXT_OFFSET;
+        self.region[at..at + NEXT_SIZE].copy_from_slice(&link.to_le_bytes());
     }
 
-    /// Retrieve the value slice stored for `handle`.
+    /// Copy `bytes` into the payload stream of the chain starting at `head`,
+    /// beginning `offset` bytes into the stream.
+    fn write_stream(&mut self, head: usize, mut offset: usize, mut bytes: &[u8]) {
+        let mut index = head;
+        let payload = self.payload_size();
+        while !bytes.is_empty() {
+            if offset < payload {
+                let take = bytes.len().min(payload - offset);
+                let start = self.block_offset(index) + HEADER_SIZE + offset;
+                self.region[start..start + take].copy_from_slice(&bytes[..take]);
+                bytes = &bytes[take..];
+                offset = 0;
+            } else {
+                offset -= payload;
+            }
+            if bytes.is_empty() {
+                break;
+            }
+            index = self
+                .next_block(index)
+                .expect("chain shorter than its payload");
+        }
+    }
+
+    /// Copy `len` bytes out of the payload stream of the chain starting at
+    /// `head`, beginning `offset` bytes into the stream.
     ///
-    /// The returned slice points directly into the slab's backing region. TTL
-    /// and key can be recovered by interpreting the header and key bytes if
-    /// needed.
-    pub fn get_value(&self, handle: Handle) -> Option<&[u8]> {
-        let index = handle.0;
-        if index >= self.total_blocks {
-            return None;
+    /// Returns `None` if the chain ends early.
+    fn read_stream(&self, head: usize, mut offset: usize, len: usize) -> Option<Vec<u8>> {
+        let mut out = Vec::with_capacity(len);
+        let mut index = head;
+        let payload = self.payload_size();
+        while out.len() < len {
+            if offset < payload {
+                let take = (len - out.len()).min(payload - offset);
+                let start = self.block_offset(index) + HEADER_SIZE + offset;
+                out.extend_from_slice(&self.region[start..start + take]);
+                offset = 0;
+            } else {
+                offset -= payload;
+            }
+            if out.len() < len {
+                index = self.next_block(index)?;
+            }
         }
-        let offset = index * BLOCK_SIZE;
-        let block = &self.region[offset..offset + BLOCK_SIZE];
+        Some(out)
+    }
+
+    /// Retrieve the value stored for `handle`.
+    ///
+    /// For entries held in a single block the returned value borrows directly
+    /// from the slab's backing region; chained entries are copied out into an
+    /// owned buffer. TTL and key can be recovered by interpreting the header
+    /// and key bytes if needed.
+    pub fn get_value(&self, handle: Handle) -> Option<Cow<'_, [u8]>> {
+        let index = self.live_index(handle)?;
+        let offset = self.block_offset(index);
+        let block = &self.region[offset..offset + self.block_size];
 
         // Decode key and value lengths to determine the slice boundaries.
-        let key_len_bytes = &block[KEY_LEN_OFFSET..KEY_LEN_OFFSET + 2];
-        let val_len_bytes = &block[VAL_LEN_OFFSET..VAL_LEN_OFFSET + 2];
-        let key_len = u16::from_le_bytes([key_len_bytes[0], key_len_bytes[1]]) as usize;
-        let val_len = u16::from_le_bytes([val_len_bytes[0], val_len_bytes[1]]) as usize;
+        let header = BlockHeader::decode(block);
+        let key_len = header.key_len as usize;
+        let val_len = header.val_len as usize;
 
-        let val_start = HEADER_SIZE + key_len;
+        let val_start = self.value_start(key_len);
+        if header.chained {
+            return self
+                .read_stream(index, val_start - HEADER_SIZE, val_len)
+                .map(Cow::Owned);
+        }
         let val_end = val_start + val_len;
-        if val_end > BLOCK_SIZE {
+        if val_end > self.block_size {
             return None;
         }
 
-        Some(&block[val_start..val_end])
+        Some(Cow::Borrowed(&block[val_start..val_end]))
     }
 
     /// Retrieve all metadata and payload for `handle`.
-    pub fn get_meta(&self, handle: Handle) -> Option<(u64, &[u8], &[u8])> {
-        let index = handle.0;
-        if index >= self.total_blocks {
-            return None;
-        }
-        let offset = index * BLOCK_SIZE;
-        let block = &self.region[offset..offset + BLOCK_SIZE];
-
-        // Extract TTL.
-        let ttl_bytes = &block[TTL_OFFSET..TTL_OFFSET + TTL_SIZE];
-        let ttl = u64::from_le_bytes(ttl_bytes.try_into().ok()?);
+    ///
+    /// Returns the absolute expiry in milliseconds since the UNIX epoch along
+    /// with the key and value, borrowed or copied as for [`Slab::get_value`].
+    pub fn get_meta(&self, handle: Handle) -> Option<Meta<'_>> {
+        let index = self.live_index(handle)?;
+        let offset = self.block_offset(index);
+        let block = &self.region[offset..offset + self.block_size];
 
-        // Extract lengths.
-        let key_len_bytes = &block[KEY_LEN_OFFSET..KEY_LEN_OFFSET + 2];
-        let val_len_bytes = &block[VAL_LEN_OFFSET..VAL_LEN_OFFSET + 2];
-        let key_len = u16::from_le_bytes([key_len_bytes[0], key_len_bytes[1]]) as usize;
-        let val_len = u16::from_le_bytes([val_len_bytes[0], val_len_bytes[1]]) as usize;
+        // Extract TTL and lengths.
+        let header = BlockHeader::decode(block);
+        let key_len = header.key_len as usize;
+        let val_len = header.val_len as usize;
 
         let key_start = HEADER_SIZE;
         let key_end = key_start + key_len;
-        let val_start = key_end;
+        let val_start = self.value_start(key_len);
+        if header.chained {
+            let key = self.read_stream(index, 0, key_len)?;
+            let value = self.read_stream(index, val_start - HEADER_SIZE, val_len)?;
+            return Some((header.expires_at, Cow::Owned(key), Cow::Owned(value)));
+        }
         let val_end = val_start + val_len;
-        if val_end > BLOCK_SIZE {
+        if val_end > self.block_size {
             return None;
         }
 
         let key = &block[key_start..key_end];
         let value = &block[val_start..val_end];
 
-        Some((ttl, key, value))
+        Some((header.expires_at, Cow::Borrowed(key), Cow::Borrowed(value)))
     }
 
-    /// Deallocate the block referenced by `handle` and return it to the freelist.
-    pub fn deallocate(&mut self, handle: Handle) {
-        let index = handle.0;
-        if index >= self.total_blocks {
-            return;
+    /// Like [`Slab::get_value`], but returns `None` if the entry has expired
+    /// at `now` (milliseconds since the UNIX epoch).
+    pub fn get_value_at(&self, handle: Handle, now: u64) -> Option<Cow<'_, [u8]>> {
+        let header = self.header(handle)?;
+        if Self::is_expired(header.expires_at, now) {
+            return None;
         }
-        assert!(
-            !self.free_list.contains(&index),
-            "block already freed"
-        );
-        let offset = index * BLOCK_SIZE;
-        // Zero out the block for predictability; keeps hot path free of mallocs.
-        self.region[offset..offset + BLOCK_SIZE].fill(0);
-        self.free_list.push(index);
+        self.get_value(handle)
     }
 
-    /// Dump the raw contents of the block for debugging purposes.
-    pub fn debug_dump(&self, handle: Handle) -> Option<String> {
-        let index = handle.0;
-        if index >= self.total_blocks {
+    /// Like [`Slab::get_meta`], but returns `None` if the entry has expired at
+    /// `now` (milliseconds since the UNIX epoch).
+    pub fn get_meta_at(&self, handle: Handle, now: u64) -> Option<Meta<'_>> {
+        let meta = self.get_meta(handle)?;
+        if Self::is_expired(meta.0, now) {
             return None;
         }
-        let offset = index * BLOCK_SIZE;
-        let block = &self.region[offset..offset + BLOCK_SIZE];
+        Some(meta)
+    }
+
+    /// Deallocate every entry that has expired at `now` (milliseconds since
+    /// the UNIX epoch) and return how many were reclaimed.
+    ///
+    /// Scans every block, so the cost is proportional to the slab capacity.
+    pub fn purge_expired(&mut self, now: u64) -> usize {
+        let mut purged = 0;
+        for index in 0..self.total_blocks {
+            if !self.is_head(index) {
+                continue;
+            }
+            let handle = self.handle_for(index);
+            let expires_at = self.header(handle).expect("live block").expires_at;
+            if Self::is_expired(expires_at, now) && self.deallocate(handle) {
+                purged += 1;
+            }
+        }
+        purged
+    }
+
+    /// Retrieve the insert sequence number `handle` was allocated at.
+    ///
+    /// Sequence numbers strictly increase across allocations from one slab.
+    pub fn get_sequence(&self, handle: Handle) -> Option<u64> {
+        self.header(handle).map(|header| header.sequence)
+    }
+
+    /// Decode the header of the block referenced by `handle` without reading
+    /// its payload.
+    pub fn header(&self, handle: Handle) -> Option<BlockHeader> {
+        let index = self.live_index(handle)?;
+        let offset = self.block_offset(index);
+        Some(BlockHeader::decode(
+            &self.region[offset..offset + HEADER_SIZE],
+        ))
+    }
+
+    /// Deallocate the block referenced by `handle`, along with any blocks
+    /// chained after it, and return them to the freelist.
+    ///
+    /// Returns `false` without freeing anything if the handle is out of range
+    /// or stale, i.e. its block was freed and has since been reallocated.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block is currently free.
+    pub fn deallocate(&mut self, handle: Handle) -> bool {
+        let mut index = handle.index;
+        if index >= self.total_blocks {
+            return false;
+        }
+        assert!(!self.is_free(index), "block already freed");
+        if self.generation(index) != handle.generation {
+            return false;
+        }
+        let head = self.header(handle).expect("live block");
+        self.bytes_used -= head.key_len as usize + head.val_len as usize;
+        loop {
+            let next = self.next_block(index);
+            let generation = self.generation(index).wrapping_add(1);
+            let offset = self.block_offset(index);
+            // Zero out the block for predictability; keeps hot path free of mallocs.
+            self.region[offset..offset + self.block_size].fill(0);
+            self.region[offset + GEN_OFFSET..offset + GEN_OFFSET + GEN_SIZE]
+                .copy_from_slice(&generation.to_le_bytes());
+            self.free_list.push(index);
+            self.free_bits[index / 64] |= 1 << (index % 64);
+            self.bytes_used -= HEADER_SIZE;
+            match next {
+                Some(next) => index = next,
+                None => return true,
+            }
+        }
+    }
+
+    /// Report free blocks per size class and the longest run of adjacent free
+    /// blocks, to help decide when compaction is worthwhile.
+    pub fn fragmentation_report(&self) -> FragmentationReport {
+        let mut largest_free_run = 0;
+        let mut run = 0;
+        for index in 0..self.total_blocks {
+            run = if self.is_free(index) { run + 1 } else { 0 };
+            largest_free_run = largest_free_run.max(run);
+        }
+
+        FragmentationReport {
+            free_by_size_class: vec![(self.block_size, self.free_list.len())],
+            largest_free_run,
+        }
+    }
+
+    /// Gather free space, load factor, largest stored value and
+    /// fragmentation in one snapshot, for logging when allocation fails.
+    ///
+    /// Scans every block, so this is meant for the failure path rather than
+    /// routine monitoring.
+    pub fn failure_diagnostics(&self) -> FailureDiagnostics {
+        let largest_value = (0..self.total_blocks)
+            .filter(|&index| self.is_head(index))
+            .filter_map(|index| self.header(self.handle_for(index)))
+            .map(|header| header.val_len as usize)
+            .max()
+            .unwrap_or(0);
+        FailureDiagnostics {
+            free_blocks: self.free_blocks(),
+            load_factor: self.load_factor(),
+            largest_value,
+            fragmentation: self.fragmentation_report(),
+        }
+    }
+
+    /// Dump the raw contents of the head block for debugging purposes.
+    pub fn debug_dump(&self, handle: Handle) -> Option<String> {
+        let index = self.live_index(handle)?;
+        let offset = self.block_offset(index);
+        let block = &self.region[offset..offset + self.block_size];
         let mut out = String::new();
         for (i, chunk) in block.chunks(16).enumerate() {
             let hex: String = chunk.iter().map(|b| format!("{:02x} ", b)).collect();
             let ascii: String = chunk
                 .iter()
-                .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
                 .collect();
             out.push_str(&format!("{:04x}: {:<48} {}\n", i * 16, hex, ascii));
         }
@@ -206,18 +885,395 @@ mod tests {
         let mut slab = Slab::new(1024); // 2 blocks
         let handle = slab.allocate(b"key", b"value", 1).expect("allocation");
         let val = slab.get_value(handle).expect("get");
-        assert_eq!(val, b"value");
+        assert_eq!(&*val, b"value");
 
         let (ttl, key, value) = slab.get_meta(handle).expect("meta");
         assert_eq!(ttl, 1);
-        assert_eq!(key, b"key");
-        assert_eq!(value, b"value");
+        assert_eq!(&*key, b"key");
+        assert_eq!(&*value, b"value");
 
         slab.deallocate(handle);
-        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-            slab.deallocate(handle)
-        }));
+        let result =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| slab.deallocate(handle)));
         assert!(result.is_err(), "double free should panic");
         assert_eq!(slab.free_list.len(), slab.total_blocks);
     }
+
+    #[test]
+    fn freed_bits_track_reuse() {
+        // 70 blocks so the bitmap spans a partial second word.
+        let mut slab = Slab::new(70 * DEFAULT_BLOCK_SIZE);
+        let handles: Vec<_> = (0..70)
+            .map(|_| slab.allocate(b"k", b"v", 0).expect("allocation"))
+            .collect();
+        assert!(slab.free_bits.iter().all(|&word| word == 0));
+
+        let last = handles[69];
+        slab.deallocate(last);
+        let reused = slab.allocate(b"k", b"v", 0).expect("allocation");
+        assert_eq!(reused.index, last.index);
+        // Freeing the reallocated block is not a double free.
+        slab.deallocate(reused);
+        let result =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| slab.deallocate(reused)));
+        assert!(result.is_err(), "double free should panic");
+    }
+
+    #[test]
+    fn sequence_numbers_strictly_increase() {
+        let mut slab = Slab::new(2 * DEFAULT_BLOCK_SIZE);
+        let first = slab.allocate(b"a", b"1", 0).expect("allocation");
+        let second = slab.allocate(b"b", b"2", 0).expect("allocation");
+        let first_seq = slab.get_sequence(first).expect("sequence");
+        let second_seq = slab.get_sequence(second).expect("sequence");
+        assert!(second_seq > first_seq);
+
+        // Reusing a freed block still takes a fresh, larger number.
+        slab.deallocate(first);
+        let third = slab.allocate(b"c", b"3", 0).expect("allocation");
+        assert_eq!(third.index, first.index);
+        assert!(slab.get_sequence(third).expect("sequence") > second_seq);
+    }
+
+    #[test]
+    fn aligned_values_start_on_boundary() {
+        let mut slab = Slab::with_value_alignment(2 * DEFAULT_BLOCK_SIZE, 16);
+        let handle = slab.allocate(b"abc", b"payload", 0).expect("allocation");
+        let value = slab.get_value(handle).expect("get");
+        assert_eq!(&*value, b"payload");
+        assert_eq!(value.as_ptr() as usize % 16, 0);
+
+        // Alignment beyond the allocator's, combined with small blocks.
+        let mut small = Slab::with_layout(8 * 128, 128, 64);
+        assert_eq!(small.block_size(), 128);
+        for key in [&b"a"[..], b"bb", b"ccc"] {
+            let handle = small.allocate(key, b"payload", 0).expect("allocation");
+            let value = small.get_value(handle).expect("get");
+            assert_eq!(value.as_ptr() as usize % 64, 0);
+        }
+
+        let (_, key, value) = slab.get_meta(handle).expect("meta");
+        assert_eq!(&*key, b"abc");
+        assert_eq!(&*value, b"payload");
+
+        // Padding counts against the block: this fits one block unaligned but
+        // needs a second one at 64.
+        let value = vec![0u8; DEFAULT_BLOCK_SIZE - HEADER_SIZE - 1];
+        assert!(
+            Slab::new(DEFAULT_BLOCK_SIZE)
+                .allocate(b"k", &value, 0)
+                .is_some()
+        );
+        let mut slab = Slab::with_value_alignment(DEFAULT_BLOCK_SIZE, 64);
+        assert!(slab.allocate(b"k", &value, 0).is_none());
+    }
+
+    #[test]
+    fn relative_ttl_is_stored_as_absolute_expiry() {
+        let mut slab = Slab::new(2 * DEFAULT_BLOCK_SIZE);
+        let ttl = Duration::from_secs(30);
+        let early = slab
+            .allocate_with_ttl(b"a", b"1", ttl, 1_000)
+            .expect("allocation");
+        let late = slab
+            .allocate_with_ttl(b"b", b"2", ttl, 5_000)
+            .expect("allocation");
+
+        let (early_expiry, _, _) = slab.get_meta(early).expect("meta");
+        let (late_expiry, _, _) = slab.get_meta(late).expect("meta");
+        assert_eq!(early_expiry, 31_000);
+        assert_eq!(late_expiry, 35_000);
+    }
+
+    #[test]
+    fn header_matches_encoded_fields() {
+        let mut slab = Slab::new(2 * DEFAULT_BLOCK_SIZE);
+        slab.allocate(b"first", b"x", 0).expect("allocation");
+        let handle = slab.allocate(b"key", b"value", 42).expect("allocation");
+        let header = slab.header(handle).expect("header");
+        assert_eq!(
+            header,
+            BlockHeader {
+                expires_at: 42,
+                key_len: 3,
+                val_len: 5,
+                sequence: 1,
+                chained: false,
+                generation: 0,
+            }
+        );
+        let out_of_range = Handle {
+            index: 2,
+            generation: 0,
+        };
+        assert!(slab.header(out_of_range).is_none());
+    }
+
+    #[test]
+    fn slow_allocations_land_in_outlier_buffer() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        // Each timer reading advances the fake clock by `step` milliseconds.
+        let now = Arc::new(AtomicU64::new(0));
+        let step = Arc::new(AtomicU64::new(1));
+        let mut slab = Slab::new(4 * DEFAULT_BLOCK_SIZE);
+        let (clock, stride) = (Arc::clone(&now), Arc::clone(&step));
+        slab.track_alloc_outliers_with_timer(Duration::from_millis(10), 2, move || {
+            let ms = clock.fetch_add(stride.load(Ordering::Relaxed), Ordering::Relaxed);
+            Duration::from_millis(ms)
+        });
+
+        slab.allocate(b"fast", b"v", 0).expect("allocation");
+        assert!(slab.recent_alloc_outliers().is_empty());
+
+        step.store(50, Ordering::Relaxed);
+        slab.allocate(b"slow", b"value", 0).expect("allocation");
+        assert_eq!(
+            slab.recent_alloc_outliers(),
+            vec![AllocOutlier {
+                latency: Duration::from_millis(50),
+                requested_bytes: 9,
+                succeeded: true,
+            }]
+        );
+
+        // The buffer keeps only the most recent `capacity` outliers.
+        slab.allocate(b"k", b"", 0).expect("allocation");
+        slab.allocate(b"kk", b"", 0).expect("allocation");
+        let recent = slab.recent_alloc_outliers();
+        let sizes: Vec<_> = recent.iter().map(|o| o.requested_bytes).collect();
+        assert_eq!(sizes, vec![1, 2]);
+    }
+
+    #[test]
+    fn value_spanning_three_blocks_round_trips() {
+        let mut slab = Slab::new(4 * DEFAULT_BLOCK_SIZE);
+        let value: Vec<u8> = (0..1200u32).map(|i| (i % 251) as u8).collect();
+        assert_eq!(slab.blocks_needed(3, value.len()), 3);
+        let handle = slab.allocate(b"key", &value, 7).expect("allocation");
+        assert_eq!(slab.free_list.len(), 1);
+        assert!(slab.header(handle).expect("header").chained);
+
+        assert_eq!(&*slab.get_value(handle).expect("get"), &value[..]);
+        let (ttl, key, stored) = slab.get_meta(handle).expect("meta");
+        assert_eq!(ttl, 7);
+        assert_eq!(&*key, b"key");
+        assert_eq!(&*stored, &value[..]);
+
+        // Not enough free blocks left for another chain; nothing is consumed.
+        assert!(slab.allocate(b"key", &value, 0).is_none());
+        assert_eq!(slab.free_list.len(), 1);
+
+        slab.deallocate(handle);
+        assert_eq!(slab.free_list.len(), slab.total_blocks);
+        for index in 0..slab.total_blocks {
+            let offset = slab.block_offset(index);
+            let block = &slab.region[offset..offset + DEFAULT_BLOCK_SIZE];
+            assert!(block[..GEN_OFFSET].iter().all(|&b| b == 0));
+            assert!(block[HEADER_SIZE..].iter().all(|&b| b == 0));
+        }
+    }
+
+    #[test]
+    fn value_one_byte_over_single_block_round_trips() {
+        let mut slab = Slab::new(2 * DEFAULT_BLOCK_SIZE);
+        let fits = vec![0xab; DEFAULT_BLOCK_SIZE - HEADER_SIZE - 1];
+        assert_eq!(slab.blocks_needed(1, fits.len()), 1);
+        assert_eq!(slab.blocks_needed(1, fits.len() + 1), 2);
+        let handle = slab.allocate(b"k", &fits, 0).expect("allocation");
+        assert!(matches!(slab.get_value(handle), Some(Cow::Borrowed(_))));
+        slab.deallocate(handle);
+
+        let over = vec![0xcd; DEFAULT_BLOCK_SIZE - HEADER_SIZE];
+        let handle = slab.allocate(b"k", &over, 0).expect("allocation");
+        assert!(slab.free_list.is_empty());
+        assert_eq!(&*slab.get_value(handle).expect("get"), &over[..]);
+        slab.deallocate(handle);
+        assert_eq!(slab.free_list.len(), slab.total_blocks);
+    }
+
+    #[test]
+    fn stale_handle_does_not_see_reused_block() {
+        let mut slab = Slab::new(DEFAULT_BLOCK_SIZE);
+        let old = slab.allocate(b"old", b"first", 0).expect("allocation");
+        assert!(slab.deallocate(old));
+        assert!(slab.get_value(old).is_none());
+
+        let new = slab.allocate(b"new", b"second", 0).expect("allocation");
+        assert_eq!(new.index, old.index);
+        assert_ne!(new, old);
+        assert!(slab.get_value(old).is_none());
+        assert!(slab.get_meta(old).is_none());
+        assert!(slab.header(old).is_none());
+        assert_eq!(&*slab.get_value(new).expect("get"), b"second");
+
+        // Freeing through the stale handle leaves the new occupant alone.
+        assert!(!slab.deallocate(old));
+        assert_eq!(&*slab.get_value(new).expect("get"), b"second");
+        assert_eq!(slab.header(new).expect("header").generation, 1);
+    }
+
+    #[test]
+    fn usable_payload_is_largest_single_block_value() {
+        for slab in [
+            Slab::new(2 * DEFAULT_BLOCK_SIZE),
+            Slab::with_value_alignment(2 * DEFAULT_BLOCK_SIZE, 64),
+        ] {
+            let mut slab = slab;
+            let usable = slab.usable_payload_bytes();
+            let fits = vec![1u8; usable];
+            let handle = slab.allocate(b"", &fits, 0).expect("allocation");
+            assert!(!slab.header(handle).expect("header").chained);
+            slab.deallocate(handle);
+
+            let over = vec![1u8; usable + 1];
+            let handle = slab.allocate(b"", &over, 0).expect("allocation");
+            assert!(slab.header(handle).expect("header").chained);
+        }
+        assert_eq!(
+            Slab::new(DEFAULT_BLOCK_SIZE).usable_payload_bytes(),
+            DEFAULT_BLOCK_SIZE - HEADER_SIZE
+        );
+        assert_eq!(
+            Slab::with_value_alignment(DEFAULT_BLOCK_SIZE, 64).usable_payload_bytes(),
+            DEFAULT_BLOCK_SIZE - 64
+        );
+    }
+
+    #[test]
+    fn occupancy_counters_follow_allocations() {
+        let mut slab = Slab::new(4 * DEFAULT_BLOCK_SIZE + 100);
+        assert_eq!(slab.capacity(), 4);
+        assert!(slab.is_empty());
+        assert_eq!(slab.bytes_used(), 0);
+
+        let small = slab.allocate(b"key", b"value", 0).expect("allocation");
+        let big = slab.allocate(b"k", &[7; 600], 0).expect("allocation");
+        assert_eq!(slab.len(), 3);
+        assert_eq!(slab.free_blocks(), 1);
+        assert_eq!(
+            slab.bytes_used(),
+            (HEADER_SIZE + 8) + (2 * HEADER_SIZE + 601)
+        );
+
+        // A failed allocation changes nothing.
+        assert!(slab.allocate(b"k", &[7; 600], 0).is_none());
+        assert_eq!(slab.len(), 3);
+        assert_eq!(
+            slab.bytes_used(),
+            (HEADER_SIZE + 8) + (2 * HEADER_SIZE + 601)
+        );
+
+        slab.allocate(b"a", b"b", 0).expect("allocation");
+        assert!(slab.is_full());
+        assert_eq!(slab.free_blocks(), 0);
+
+        slab.deallocate(big);
+        slab.deallocate(small);
+        assert!(!slab.is_full());
+        assert_eq!(slab.len(), 1);
+        assert_eq!(slab.free_blocks(), 3);
+        assert_eq!(slab.bytes_used(), HEADER_SIZE + 2);
+    }
+
+    #[test]
+    fn expired_entries_are_hidden_and_purged() {
+        let mut slab = Slab::new(4 * DEFAULT_BLOCK_SIZE);
+        let forever = slab.allocate(b"forever", b"v", 0).expect("allocation");
+        let early = slab.allocate(b"early", b"v", 100).expect("allocation");
+        let late = slab.allocate(b"late", &[1; 600], 200).expect("allocation");
+
+        // Expiry is inclusive: an entry expiring at 100 is gone at 100.
+        assert!(slab.get_value_at(early, 99).is_some());
+        assert!(slab.get_value_at(early, 100).is_none());
+        assert!(slab.get_meta_at(early, 100).is_none());
+        assert!(slab.get_value(early).is_some());
+        assert!(slab.get_value_at(forever, u64::MAX).is_some());
+        assert_eq!(slab.len(), 4);
+
+        assert_eq!(slab.purge_expired(150), 1);
+        assert!(slab.get_value(early).is_none());
+        assert_eq!(&*slab.get_meta_at(late, 150).expect("meta").1, b"late");
+
+        // The chained entry's continuation block is freed with its head.
+        assert_eq!(slab.purge_expired(200), 1);
+        assert_eq!(slab.len(), 1);
+        assert_eq!(slab.purge_expired(u64::MAX), 0);
+        assert!(slab.get_value(forever).is_some());
+    }
+
+    #[test]
+    fn failure_diagnostics_after_exhaustion() {
+        let mut slab = Slab::new(4 * DEFAULT_BLOCK_SIZE);
+        slab.allocate(b"a", &[0; 10], 0).expect("allocation");
+        slab.allocate(b"b", &[0; 700], 0).expect("allocation");
+        assert_eq!(slab.load_factor(), 0.75);
+        slab.allocate(b"c", &[0; 20], 0).expect("allocation");
+        assert!(slab.allocate(b"d", &[0; 20], 0).is_none());
+
+        let diagnostics = slab.failure_diagnostics();
+        assert_eq!(diagnostics.free_blocks, 0);
+        assert_eq!(diagnostics.load_factor, 1.0);
+        assert_eq!(diagnostics.largest_value, 700);
+        assert_eq!(diagnostics.fragmentation.largest_free_run, 0);
+    }
+
+    #[test]
+    fn fragmentation_report_finds_longest_free_run() {
+        let mut slab = Slab::new(8 * DEFAULT_BLOCK_SIZE);
+        let handles: Vec<_> = (0..8)
+            .map(|_| slab.allocate(b"k", b"v", 0).expect("allocation"))
+            .collect();
+        let report = slab.fragmentation_report();
+        assert_eq!(report.free_by_size_class, vec![(DEFAULT_BLOCK_SIZE, 0)]);
+        assert_eq!(report.largest_free_run, 0);
+
+        // Leave blocks 2 and 6 live: free runs are [0, 1], [3, 4, 5] and [7].
+        for i in [0, 1, 3, 4, 5, 7] {
+            slab.deallocate(handles[i]);
+        }
+        let report = slab.fragmentation_report();
+        assert_eq!(report.free_by_size_class, vec![(DEFAULT_BLOCK_SIZE, 6)]);
+        assert_eq!(report.largest_free_run, 3);
+    }
+
+    #[test]
+    fn small_blocks_reject_oversized_values() {
+        let mut slab = Slab::with_block_size(4 * 64, 64);
+        assert_eq!(slab.capacity(), 4);
+        assert_eq!(slab.usable_payload_bytes(), 64 - HEADER_SIZE);
+
+        // Four blocks carry 148 payload bytes between them.
+        assert!(slab.allocate(b"k", &[1; 200], 0).is_none());
+        let handle = slab.allocate(b"k", &[2; 100], 0).expect("allocation");
+        assert!(slab.header(handle).expect("header").chained);
+        assert_eq!(slab.len(), 3);
+        assert_eq!(slab.get_value(handle).as_deref(), Some(&[2; 100][..]));
+    }
+
+    #[test]
+    fn large_blocks_hold_big_values_in_one_block() {
+        let mut slab = Slab::with_block_size(4 * 4096, 4096);
+        let value = vec![0x5a; 2048];
+        let handle = slab.allocate(b"blob", &value, 0).expect("allocation");
+        assert_eq!(slab.len(), 1);
+        assert!(!slab.header(handle).expect("header").chained);
+        assert_eq!(slab.get_value(handle).as_deref(), Some(&value[..]));
+        assert!(slab.deallocate(handle));
+        assert_eq!(
+            slab.fragmentation_report().free_by_size_class,
+            vec![(4096, 4)]
+        );
+    }
+
+    #[test]
+    fn block_size_must_be_power_of_two_above_header() {
+        for block_size in [HEADER_SIZE, 16, 48, 100] {
+            let result = std::panic::catch_unwind(|| Slab::with_block_size(4096, block_size));
+            assert!(result.is_err(), "block size {block_size} was accepted");
+        }
+        assert_eq!(Slab::with_block_size(4096, 32).block_size(), 32);
+        assert_eq!(Slab::new(4096).block_size(), DEFAULT_BLOCK_SIZE);
+    }
 }
@@ -1,6 +1,139 @@
 use cortex_api::EvictionPolicy;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
 
 /// Least-frequently-used eviction policy.
-pub struct Lfu;
+///
+/// Every tracked key has an access count, starting at one on insert. Keys are
+/// kept ordered by `(count, last touch)`, so the victim is the key with the
+/// fewest accesses, ties going to the one touched longest ago. Accesses and
+/// evictions are O(log n).
+pub struct Lfu<K> {
+    /// Access count and last-touch stamp of each tracked key.
+    entries: HashMap<K, (u64, u64)>,
+    /// Tracked keys ordered coldest first.
+    order: BTreeMap<(u64, u64), K>,
+    /// Stamp handed to the next touched key.
+    tick: u64,
+}
 
-impl EvictionPolicy for Lfu {}
+impl<K: Clone + Eq + Hash> Lfu<K> {
+    /// Create an empty policy.
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Number of keys being tracked.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no keys are being tracked.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Access count of `key`, if tracked.
+    pub fn frequency(&self, key: &K) -> Option<u64> {
+        self.entries.get(key).map(|&(count, _)| count)
+    }
+
+    /// Record `key` at `count` accesses as the most recently touched.
+    fn touch(&mut self, key: K, count: u64) {
+        let rank = (count, self.tick);
+        self.tick += 1;
+        self.order.insert(rank, key.clone());
+        self.entries.insert(key, rank);
+    }
+}
+
+impl<K: Clone + Eq + Hash> Default for Lfu<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Clone + Eq + Hash> EvictionPolicy<K> for Lfu<K> {
+    fn on_insert(&mut self, key: K) {
+        if self.entries.contains_key(&key) {
+            self.on_access(&key);
+        } else {
+            self.touch(key, 1);
+        }
+    }
+
+    fn on_access(&mut self, key: &K) {
+        if let Some(rank) = self.entries.get(key).copied() {
+            let key = self.order.remove(&rank).expect("ranked key");
+            self.touch(key, rank.0 + 1);
+        }
+    }
+
+    fn on_remove(&mut self, key: &K) {
+        if let Some(rank) = self.entries.remove(key) {
+            self.order.remove(&rank);
+        }
+    }
+
+    fn evict(&mut self) -> Option<K> {
+        let (_, key) = self.order.pop_first()?;
+        self.entries.remove(&key);
+        Some(key)
+    }
+
+    fn evict_batch(&mut self, n: usize) -> Vec<K> {
+        let mut victims = Vec::with_capacity(n.min(self.len()));
+        while victims.len() < n {
+            let Some((_, key)) = self.order.pop_first() else {
+                break;
+            };
+            self.entries.remove(&key);
+            victims.push(key);
+        }
+        victims
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_returns_lowest_frequencies_in_order() {
+        let mut lfu = Lfu::new();
+        // Key `i` ends up with `i + 1` accesses; insert in shuffled order.
+        for key in [4u32, 6, 0, 2, 5, 1, 3] {
+            lfu.on_insert(key);
+            for _ in 0..key {
+                lfu.on_access(&key);
+            }
+        }
+        assert_eq!(lfu.frequency(&6), Some(7));
+
+        assert_eq!(lfu.evict_batch(5), vec![0, 1, 2, 3, 4]);
+        assert_eq!(lfu.len(), 2);
+        for key in 0..5 {
+            assert_eq!(lfu.frequency(&key), None);
+        }
+        assert_eq!(lfu.evict_batch(5), vec![5, 6]);
+        assert!(lfu.is_empty());
+    }
+
+    #[test]
+    fn ties_break_towards_least_recently_touched() {
+        let mut lfu = Lfu::new();
+        for key in ['A', 'B', 'C'] {
+            lfu.on_insert(key);
+        }
+        lfu.on_access(&'A');
+        lfu.on_access(&'B');
+        lfu.on_remove(&'C');
+        assert_eq!(lfu.evict(), Some('A'));
+        assert_eq!(lfu.evict(), Some('B'));
+        assert_eq!(lfu.evict(), None);
+    }
+}
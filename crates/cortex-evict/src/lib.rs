@@ -3,13 +3,16 @@
 
 //! Built-in eviction policies for the Cortex cache.
 
+/// Least-frequently-used eviction policy.
+pub mod lfu;
+/// Least-recently-used eviction policy.
+pub mod lru;
 /// Random eviction policy implementation.
 pub mod random;
 /// Time-to-live eviction policy.
 pub mod ttl;
-/// Least-frequently-used eviction policy.
-pub mod lfu;
 
+pub use lfu::Lfu;
+pub use lru::Lru;
 pub use random::Random;
 pub use ttl::Ttl;
-pub use lfu::Lfu;
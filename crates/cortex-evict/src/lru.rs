@@ -0,0 +1,228 @@
+use cortex_api::EvictionPolicy;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Slot index marking either end of the recency list.
+const NIL: usize = usize::MAX;
+
+/// Position of one tracked key in the recency list.
+struct Node<K> {
+    key: K,
+    /// Slot of the next more recently used key.
+    newer: usize,
+    /// Slot of the next less recently used key.
+    older: usize,
+}
+
+/// Least-recently-used eviction policy.
+///
+/// Tracked keys form a doubly-linked list ordered by recency. Nodes live in a
+/// dense slot vector indexed by a key-to-slot map, so inserts, accesses,
+/// removals and evictions are all O(1).
+pub struct Lru<K> {
+    slots: HashMap<K, usize>,
+    nodes: Vec<Node<K>>,
+    /// Most recently used slot.
+    head: usize,
+    /// Least recently used slot.
+    tail: usize,
+}
+
+impl<K: Clone + Eq + Hash> Lru<K> {
+    /// Create an empty policy.
+    pub fn new() -> Self {
+        Self {
+            slots: HashMap::new(),
+            nodes: Vec::new(),
+            head: NIL,
+            tail: NIL,
+        }
+    }
+
+    /// Number of keys being tracked.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Whether no keys are being tracked.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Detach `slot` from its neighbours.
+    fn unlink(&mut self, slot: usize) {
+        let Node { newer, older, .. } = self.nodes[slot];
+        match newer {
+            NIL => self.head = older,
+            newer => self.nodes[newer].older = older,
+        }
+        match older {
+            NIL => self.tail = newer,
+            older => self.nodes[older].newer = newer,
+        }
+    }
+
+    /// Link a detached `slot` in as the most recently used.
+    fn push_front(&mut self, slot: usize) {
+        self.nodes[slot].newer = NIL;
+        self.nodes[slot].older = self.head;
+        match self.head {
+            NIL => self.tail = slot,
+            head => self.nodes[head].newer = slot,
+        }
+        self.head = slot;
+    }
+
+    /// Unlink and drop `slot`, returning its key.
+    ///
+    /// The last node is moved into the vacated slot to keep `nodes` dense.
+    fn remove_slot(&mut self, slot: usize) -> K {
+        self.unlink(slot);
+        let node = self.nodes.swap_remove(slot);
+        self.slots.remove(&node.key);
+        if let Some(moved) = self.nodes.get(slot) {
+            let Node { newer, older, .. } = *moved;
+            match newer {
+                NIL => self.head = slot,
+                newer => self.nodes[newer].older = slot,
+            }
+            match older {
+                NIL => self.tail = slot,
+                older => self.nodes[older].newer = slot,
+            }
+            if let Some(entry) = self.slots.get_mut(&self.nodes[slot].key) {
+                *entry = slot;
+            }
+        }
+        node.key
+    }
+}
+
+impl<K: Clone + Eq + Hash> Default for Lru<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Clone + Eq + Hash> EvictionPolicy<K> for Lru<K> {
+    fn on_insert(&mut self, key: K) {
+        if let Some(&slot) = self.slots.get(&key) {
+            self.unlink(slot);
+            self.push_front(slot);
+            return;
+        }
+        let slot = self.nodes.len();
+        self.nodes.push(Node {
+            key: key.clone(),
+            newer: NIL,
+            older: NIL,
+        });
+        self.slots.insert(key, slot);
+        self.push_front(slot);
+    }
+
+    fn on_access(&mut self, key: &K) {
+        if let Some(&slot) = self.slots.get(key) {
+            self.unlink(slot);
+            self.push_front(slot);
+        }
+    }
+
+    fn on_remove(&mut self, key: &K) {
+        if let Some(&slot) = self.slots.get(key) {
+            self.remove_slot(slot);
+        }
+    }
+
+    fn evict(&mut self) -> Option<K> {
+        match self.tail {
+            NIL => None,
+            tail => Some(self.remove_slot(tail)),
+        }
+    }
+
+    fn evict_batch(&mut self, n: usize) -> Vec<K> {
+        let mut victims = Vec::with_capacity(n.min(self.len()));
+        while victims.len() < n && self.tail != NIL {
+            victims.push(self.remove_slot(self.tail));
+        }
+        victims
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replay `refs` against a cache holding `capacity` keys, returning the
+    /// evicted keys in order.
+    fn simulate(lru: &mut Lru<char>, capacity: usize, refs: &str) -> Vec<char> {
+        let mut evicted = Vec::new();
+        for key in refs.chars() {
+            if lru.slots.contains_key(&key) {
+                lru.on_access(&key);
+                continue;
+            }
+            if lru.len() == capacity {
+                evicted.extend(lru.evict());
+            }
+            lru.on_insert(key);
+        }
+        evicted
+    }
+
+    #[test]
+    fn abcade_evicts_least_recently_used() {
+        let mut lru = Lru::new();
+        assert_eq!(simulate(&mut lru, 3, "ABCADE"), vec!['B', 'C']);
+        assert_eq!(lru.evict(), Some('A'));
+        assert_eq!(lru.evict(), Some('D'));
+        assert_eq!(lru.evict(), Some('E'));
+        assert_eq!(lru.evict(), None);
+        assert!(lru.is_empty());
+    }
+
+    #[test]
+    fn access_then_evict_in_same_tick_keeps_order() {
+        let mut lru = Lru::new();
+        for key in ['A', 'B', 'C'] {
+            lru.on_insert(key);
+        }
+        // A is the eviction candidate until it is touched.
+        lru.on_access(&'A');
+        assert_eq!(lru.evict(), Some('B'));
+        lru.on_access(&'C');
+        assert_eq!(lru.evict(), Some('A'));
+        lru.on_insert('D');
+        assert_eq!(lru.evict(), Some('C'));
+        assert_eq!(lru.evict(), Some('D'));
+        assert_eq!(lru.evict(), None);
+    }
+
+    #[test]
+    fn batch_eviction_takes_oldest_first() {
+        let mut lru = Lru::new();
+        for key in ['A', 'B', 'C', 'D'] {
+            lru.on_insert(key);
+        }
+        lru.on_access(&'A');
+        assert_eq!(lru.evict_batch(3), vec!['B', 'C', 'D']);
+        assert_eq!(lru.evict_batch(3), vec!['A']);
+        assert!(lru.evict_batch(1).is_empty());
+    }
+
+    #[test]
+    fn removal_relinks_neighbours() {
+        let mut lru = Lru::new();
+        for key in ['A', 'B', 'C', 'D'] {
+            lru.on_insert(key);
+        }
+        lru.on_remove(&'B');
+        lru.on_remove(&'Z');
+        lru.on_insert('A');
+        assert_eq!(lru.len(), 3);
+        assert_eq!(lru.evict(), Some('C'));
+        assert_eq!(lru.evict(), Some('D'));
+        assert_eq!(lru.evict(), Some('A'));
+    }
+}
@@ -3,4 +3,9 @@ use cortex_api::EvictionPolicy;
 /// Random eviction policy.
 pub struct Random;
 
-impl EvictionPolicy for Random {}
+impl<K> EvictionPolicy<K> for Random {
+    fn evict(&mut self) -> Option<K> {
+        // Placeholder: does not track entries yet.
+        None
+    }
+}
@@ -3,4 +3,9 @@ use cortex_api::EvictionPolicy;
 /// Time-to-live eviction policy.
 pub struct Ttl;
 
-impl EvictionPolicy for Ttl {}
+impl<K> EvictionPolicy<K> for Ttl {
+    fn evict(&mut self) -> Option<K> {
+        // Placeholder: does not track entries yet.
+        None
+    }
+}
@@ -1,2 +1,68 @@
+use crate::prometheus;
+use std::sync::atomic::{AtomicU64, Ordering};
+
 /// Lock-free counter type.
-pub struct Counter;
+#[derive(Debug, Default)]
+pub struct Counter {
+    value: AtomicU64,
+}
+
+impl Counter {
+    /// Create a counter starting at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Increment the counter by one.
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    /// Increment the counter by `n`.
+    pub fn add(&self, n: u64) {
+        self.value.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Current value.
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    /// Render the counter in Prometheus text exposition format.
+    ///
+    /// `name` is sanitized into a valid metric name and given a `_total`
+    /// suffix unless it already has one.
+    pub fn encode_prometheus(&self, name: &str, help: &str) -> String {
+        let mut name = prometheus::sanitize_name(name);
+        if !name.ends_with("_total") {
+            name.push_str("_total");
+        }
+        let mut out = String::new();
+        prometheus::write_header(&mut out, &name, help, "counter");
+        out.push_str(&format!("{name} {}\n", self.get()));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_total_suffix_once() {
+        let counter = Counter::new();
+        counter.inc();
+        counter.add(4);
+        assert_eq!(
+            counter.encode_prometheus("cache-hits", "Cache hits."),
+            "# HELP cache_hits_total Cache hits.\n\
+             # TYPE cache_hits_total counter\n\
+             cache_hits_total 5\n"
+        );
+        assert!(
+            counter
+                .encode_prometheus("hits_total", "")
+                .contains("\nhits_total 5\n")
+        );
+    }
+}
@@ -1,2 +1,227 @@
+use crate::prometheus;
+use std::sync::atomic::{AtomicU64, Ordering};
+
 /// Histogram metric.
-pub struct Histogram;
+///
+/// Samples are counted into buckets with fixed, inclusive upper boundaries.
+/// Samples above the highest boundary are not folded into a catch-all bucket
+/// but counted separately, see [`Histogram::overflow_count`].
+#[derive(Debug)]
+pub struct Histogram {
+    /// Strictly ascending, inclusive upper boundaries.
+    bounds: Box<[u64]>,
+    /// Per-bucket sample counts, one per boundary.
+    buckets: Box<[AtomicU64]>,
+    /// Samples greater than the highest boundary.
+    overflow: AtomicU64,
+    /// Total number of samples, including overflow.
+    count: AtomicU64,
+    /// Sum of all samples, wrapping on overflow.
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    /// Create a histogram with the given bucket upper boundaries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bounds` is empty or not strictly ascending.
+    pub fn new(bounds: &[u64]) -> Self {
+        assert!(!bounds.is_empty(), "histogram needs at least one bucket");
+        assert!(
+            bounds.windows(2).all(|w| w[0] < w[1]),
+            "histogram bounds must be strictly ascending"
+        );
+        Self {
+            bounds: bounds.into(),
+            buckets: bounds.iter().map(|_| AtomicU64::new(0)).collect(),
+            overflow: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    /// Record a single sample.
+    pub fn record(&self, value: u64) {
+        let bucket = self.bounds.partition_point(|&bound| bound < value);
+        match self.buckets.get(bucket) {
+            Some(counter) => counter.fetch_add(1, Ordering::Relaxed),
+            None => self.overflow.fetch_add(1, Ordering::Relaxed),
+        };
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+    }
+
+    /// Total number of samples recorded, including overflowed ones.
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Sum of all samples recorded.
+    pub fn sum(&self) -> u64 {
+        self.sum.load(Ordering::Relaxed)
+    }
+
+    /// Number of samples that exceeded the highest finite boundary.
+    pub fn overflow_count(&self) -> u64 {
+        self.overflow.load(Ordering::Relaxed)
+    }
+
+    /// Estimate the `q`-quantile of the recorded samples.
+    ///
+    /// `q` is clamped into `[0, 1]`. Returns `None` if nothing has been
+    /// recorded.
+    ///
+    /// The result is an estimate, not an exact order statistic: the bucket
+    /// holding the target rank is found from the bucket counts, and the value
+    /// is linearly interpolated between that bucket's lower and upper
+    /// boundaries, assuming samples are spread evenly within it. The error is
+    /// therefore bounded by the width of that bucket. Ranks that fall among
+    /// overflowed samples report the highest finite boundary.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect();
+        let total = counts.iter().sum::<u64>() + self.overflow_count();
+        if total == 0 {
+            return None;
+        }
+
+        let rank = q.clamp(0.0, 1.0) * total as f64;
+        let mut below = 0;
+        let mut lower = 0;
+        for (&upper, &count) in self.bounds.iter().zip(&counts) {
+            if count > 0 && (below + count) as f64 >= rank {
+                let fraction = (rank - below as f64).max(0.0) / count as f64;
+                return Some(lower as f64 + (upper - lower) as f64 * fraction);
+            }
+            below += count;
+            lower = upper;
+        }
+        Some(lower as f64)
+    }
+
+    /// Estimated median, see [`Histogram::quantile`].
+    pub fn p50(&self) -> Option<f64> {
+        self.quantile(0.5)
+    }
+
+    /// Estimated 90th percentile, see [`Histogram::quantile`].
+    pub fn p90(&self) -> Option<f64> {
+        self.quantile(0.9)
+    }
+
+    /// Estimated 99th percentile, see [`Histogram::quantile`].
+    pub fn p99(&self) -> Option<f64> {
+        self.quantile(0.99)
+    }
+
+    /// Render the histogram in Prometheus text exposition format.
+    ///
+    /// Emits one cumulative `_bucket` line per boundary plus `le="+Inf"`,
+    /// followed by `_sum` and `_count`. Counts are read from the individual
+    /// buckets, so `+Inf` and `_count` agree even while samples are being
+    /// recorded concurrently.
+    pub fn encode_prometheus(&self, name: &str, help: &str) -> String {
+        let name = prometheus::sanitize_name(name);
+        let mut out = String::new();
+        prometheus::write_header(&mut out, &name, help, "histogram");
+
+        let mut cumulative = 0;
+        for (bound, bucket) in self.bounds.iter().zip(self.buckets.iter()) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let le = prometheus::escape_label_value(&bound.to_string());
+            out.push_str(&format!("{name}_bucket{{le=\"{le}\"}} {cumulative}\n"));
+        }
+        let total = cumulative + self.overflow_count();
+        out.push_str(&format!("{name}_bucket{{le=\"+Inf\"}} {total}\n"));
+        out.push_str(&format!("{name}_sum {}\n", self.sum()));
+        out.push_str(&format!("{name}_count {total}\n"));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_above_max_bound_counts_as_overflow() {
+        let histogram = Histogram::new(&[10, 100, 1_000]);
+        histogram.record(10);
+        histogram.record(1_000);
+        assert_eq!(histogram.overflow_count(), 0);
+
+        histogram.record(1_001);
+        assert_eq!(histogram.overflow_count(), 1);
+        assert_eq!(histogram.count(), 3);
+        assert_eq!(histogram.sum(), 2_011);
+    }
+
+    #[test]
+    fn quantiles_land_in_expected_buckets() {
+        let histogram = Histogram::new(&[1, 2, 5, 10, 20, 50, 100, 200, 500, 1_000]);
+        assert_eq!(histogram.p50(), None);
+
+        for sample in 1..=1_000 {
+            histogram.record(sample);
+        }
+        let p50 = histogram.p50().expect("p50");
+        assert!(200.0 < p50 && p50 <= 500.0, "p50 = {p50}");
+        let p90 = histogram.p90().expect("p90");
+        assert!(500.0 < p90 && p90 <= 1_000.0, "p90 = {p90}");
+        let p99 = histogram.p99().expect("p99");
+        assert!(500.0 < p99 && p99 <= 1_000.0, "p99 = {p99}");
+        // Samples are uniform within each bucket, so interpolation is exact.
+        assert_eq!(p50, 500.0);
+        assert_eq!(p99, 990.0);
+
+        assert_eq!(histogram.quantile(-1.0), histogram.quantile(0.0));
+        assert_eq!(histogram.quantile(2.0), Some(1_000.0));
+        assert!(histogram.quantile(0.0).expect("min") <= 1.0);
+    }
+
+    #[test]
+    fn quantile_in_overflow_reports_highest_bound() {
+        let histogram = Histogram::new(&[10, 100]);
+        histogram.record(5);
+        histogram.record(5_000);
+        histogram.record(9_000);
+        assert_eq!(histogram.quantile(0.25), Some(7.5));
+        assert_eq!(histogram.p90(), Some(100.0));
+    }
+
+    #[test]
+    fn prometheus_buckets_are_cumulative() {
+        let histogram = Histogram::new(&[1, 5, 10, 50]);
+        for sample in [0, 1, 2, 5, 7, 9, 11, 50, 51, 1_000] {
+            histogram.record(sample);
+        }
+        let text = histogram.encode_prometheus("op latency", "Operation \\ latency.");
+        assert!(text.starts_with(
+            "# HELP op_latency Operation \\\\ latency.\n# TYPE op_latency histogram\n"
+        ));
+
+        // Parse the bucket lines back into (le, count) pairs.
+        let buckets: Vec<(String, u64)> = text
+            .lines()
+            .filter_map(|line| line.strip_prefix("op_latency_bucket{le=\""))
+            .map(|rest| {
+                let (le, count) = rest.split_once("\"} ").expect("bucket line");
+                (le.to_string(), count.parse().expect("count"))
+            })
+            .collect();
+        let les: Vec<_> = buckets.iter().map(|(le, _)| le.as_str()).collect();
+        assert_eq!(les, ["1", "5", "10", "50", "+Inf"]);
+        assert!(buckets.windows(2).all(|w| w[0].1 <= w[1].1));
+        let counts: Vec<_> = buckets.iter().map(|&(_, count)| count).collect();
+        assert_eq!(counts, [2, 4, 6, 8, 10]);
+
+        let count_line = format!("op_latency_count {}", histogram.count());
+        assert!(text.contains(&count_line));
+        assert_eq!(buckets.last().expect("+Inf").1, histogram.count());
+        assert!(text.contains("op_latency_sum 1136\n"));
+    }
+}
@@ -7,9 +7,12 @@
 pub mod counter;
 /// Histogram metrics.
 pub mod histogram;
+#[cfg(feature = "otel")]
+mod otel;
+mod prometheus;
 /// Tracing utilities.
 pub mod trace;
 
 pub use counter::Counter;
 pub use histogram::Histogram;
-pub use trace::Tracer;
+pub use trace::{AttributeValue, Span, TraceContext, Tracer};
@@ -0,0 +1,188 @@
+//! Export of finished spans to OpenTelemetry.
+//!
+//! Spans are exported when they end, as a complete OpenTelemetry span with
+//! the original start time. Trace and span ids are taken from the
+//! [`TraceContext`](crate::TraceContext) rather than generated, and the
+//! parent is set from [`Span::parent_id`], so OpenTelemetry sees the same
+//! tree, including spans continued on other threads. A span that starts a
+//! trace is parented on the OpenTelemetry context current at that moment,
+//! so cache work nests under the application's own spans.
+
+use crate::Tracer;
+use crate::trace::{AttributeValue, Span, random_trace_id};
+use opentelemetry::global::BoxedTracer;
+use opentelemetry::trace::{
+    Span as _, SpanBuilder, SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState,
+    Tracer as _,
+};
+use opentelemetry::{Context, KeyValue};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{PoisonError, RwLock};
+use std::time::SystemTime;
+
+/// Destination for finished spans, if any.
+static EXPORTER: RwLock<Option<BoxedTracer>> = RwLock::new(None);
+
+/// Whether [`EXPORTER`] holds a tracer, readable without taking the lock.
+static EXPORTING: AtomicBool = AtomicBool::new(false);
+
+impl Tracer {
+    /// Export every span that ends from now on through `tracer`, replacing
+    /// any tracer installed before.
+    pub fn export_to<T>(tracer: T)
+    where
+        T: opentelemetry::trace::Tracer + Send + Sync + 'static,
+        T::Span: Send + Sync + 'static,
+    {
+        let tracer = BoxedTracer::new(Box::new(tracer));
+        *EXPORTER.write().unwrap_or_else(PoisonError::into_inner) = Some(tracer);
+        EXPORTING.store(true, Ordering::Release);
+    }
+
+    /// Stop exporting spans.
+    pub fn stop_export() {
+        let mut exporter = EXPORTER.write().unwrap_or_else(PoisonError::into_inner);
+        EXPORTING.store(false, Ordering::Release);
+        *exporter = None;
+    }
+}
+
+fn key_value(key: &'static str, value: AttributeValue) -> KeyValue {
+    match value {
+        AttributeValue::Bool(value) => KeyValue::new(key, value),
+        AttributeValue::Int(value) => KeyValue::new(key, value),
+        AttributeValue::Str(value) => KeyValue::new(key, value),
+    }
+}
+
+/// Whether a tracer is installed.
+pub(crate) fn is_exporting() -> bool {
+    EXPORTING.load(Ordering::Acquire)
+}
+
+/// Parent and trace id for a span starting a new trace.
+///
+/// While exporting, the span joins the application's active OpenTelemetry
+/// span, if there is one; otherwise it gets a fresh trace id.
+pub(crate) fn root_parent() -> (Option<Context>, u128) {
+    if !is_exporting() {
+        return (None, random_trace_id());
+    }
+    let cx = Context::current();
+    let span_context = cx.span().span_context().clone();
+    let trace_id = if span_context.is_valid() {
+        u128::from_be_bytes(span_context.trace_id().to_bytes())
+    } else {
+        random_trace_id()
+    };
+    (Some(cx), trace_id)
+}
+
+/// Export `span`, which is ending now, if a tracer is installed.
+pub(crate) fn export(span: &Span) {
+    if !is_exporting() {
+        return;
+    }
+    let exporter = EXPORTER.read().unwrap_or_else(PoisonError::into_inner);
+    let Some(tracer) = exporter.as_ref() else {
+        return;
+    };
+
+    let trace_id = TraceId::from(span.trace_id());
+    let parent = match span.parent_id() {
+        // A bare span context can only become a parent through
+        // `with_remote_span_context`; it is still marked local, since the
+        // parent lives in this process.
+        Some(parent_id) => Context::new().with_remote_span_context(SpanContext::new(
+            trace_id,
+            SpanId::from(parent_id),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        )),
+        None => span.root_parent().cloned().unwrap_or_default(),
+    };
+    let builder = SpanBuilder::from_name(span.name())
+        .with_trace_id(trace_id)
+        .with_span_id(SpanId::from(span.context().span_id()))
+        .with_start_time(span.started())
+        .with_attributes(
+            span.attributes()
+                .iter()
+                .map(|&(key, value)| key_value(key, value)),
+        );
+    tracer
+        .build_with_context(builder, &parent)
+        .end_with_timestamp(SystemTime::now());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::attr;
+    use opentelemetry::Value;
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+
+    #[test]
+    fn eviction_during_put_exports_as_child_span() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        Tracer::export_to(provider.tracer("cortex-test"));
+
+        let request = provider.tracer("app").start("request");
+        let request_context = request.span_context().clone();
+        let trace_id = {
+            let _guard = Context::current_with_span(request).attach();
+            let mut put = Tracer::cache_span("put", 3, 1).expect("exporting");
+            put.set_attribute(attr::HIT, false);
+            let evict = Tracer::cache_span("evict", 11, 1).expect("exporting");
+            drop(evict);
+            let mut batch = Tracer::cache_batch_span("get_many", 4, 1).expect("exporting");
+            batch.set_attribute(attr::HITS, 3usize);
+            drop(batch);
+            put.trace_id()
+        };
+        Tracer::stop_export();
+        assert_eq!(TraceId::from(trace_id), request_context.trace_id());
+
+        // Other tests may run concurrently; only look at this trace.
+        let spans: Vec<_> = exporter
+            .get_finished_spans()
+            .expect("finished spans")
+            .into_iter()
+            .filter(|span| span.span_context.trace_id() == TraceId::from(trace_id))
+            .collect();
+        let find = |name: &str| {
+            spans
+                .iter()
+                .find(|span| span.name == name)
+                .unwrap_or_else(|| panic!("no {name} span"))
+        };
+        let (request, put, evict) = (find("request"), find("put"), find("evict"));
+        let batch = find("get_many");
+        assert_eq!(spans.len(), 4);
+        assert_eq!(request.span_context, request_context);
+        assert_eq!(put.parent_span_id, request_context.span_id());
+        assert_eq!(evict.parent_span_id, put.span_context.span_id());
+        assert!(!put.parent_span_is_remote && !evict.parent_span_is_remote);
+
+        let key_len = evict
+            .attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == attr::KEY_LEN)
+            .map(|kv| kv.value.clone());
+        assert_eq!(key_len, Some(Value::I64(11)));
+        assert!(put.attributes.contains(&KeyValue::new(attr::HIT, false)));
+        assert_eq!(batch.parent_span_id, put.span_context.span_id());
+        assert!(
+            batch
+                .attributes
+                .contains(&KeyValue::new(attr::BATCH_SIZE, 4))
+        );
+        assert!(batch.attributes.contains(&KeyValue::new(attr::HITS, 3)));
+        assert!(evict.start_time >= put.start_time && evict.end_time <= put.end_time);
+    }
+}
@@ -0,0 +1,54 @@
+//! Helpers for the Prometheus text exposition format.
+
+/// Turn `name` into a valid metric name, replacing every character outside
+/// `[a-zA-Z0-9_:]` with `_` and prefixing `_` if it would start with a digit.
+pub(crate) fn sanitize_name(name: &str) -> String {
+    let mut out: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == ':' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if out.chars().next().is_none_or(|c| c.is_ascii_digit()) {
+        out.insert(0, '_');
+    }
+    out
+}
+
+/// Escape backslashes and newlines in a `# HELP` docstring.
+pub(crate) fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// Escape backslashes, double quotes and newlines in a label value.
+pub(crate) fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Write the `# HELP` and `# TYPE` header lines for a metric family.
+pub(crate) fn write_header(out: &mut String, name: &str, help: &str, kind: &str) {
+    out.push_str(&format!("# HELP {name} {}\n", escape_help(help)));
+    out.push_str(&format!("# TYPE {name} {kind}\n"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_values_are_escaped() {
+        assert_eq!(sanitize_name("cache.hits-total"), "cache_hits_total");
+        assert_eq!(sanitize_name("9lives"), "_9lives");
+        assert_eq!(sanitize_name(""), "_");
+        assert_eq!(sanitize_name("ns:ok_1"), "ns:ok_1");
+        assert_eq!(escape_help("a\\b\nc"), "a\\\\b\\nc");
+        assert_eq!(escape_label_value("say \"hi\"\n"), "say \\\"hi\\\"\\n");
+    }
+}
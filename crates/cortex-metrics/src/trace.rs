@@ -1,2 +1,379 @@
+//! Tracing utilities.
+//!
+//! Spans are grouped into traces. Every span records the trace it belongs to
+//! and the span that was current when it started, so nested work forms a
+//! tree. Trace ids are random 128-bit values and span ids random 64-bit
+//! values, so ids from different processes do not collide. The current
+//! context lives in a thread-local; to continue a trace on another thread,
+//! capture it with [`Tracer::current`], move the [`TraceContext`] across and
+//! re-enter it with [`Tracer::with_context`].
+//!
+//! Spans can carry up to [`MAX_ATTRIBUTES`](crate::trace::MAX_ATTRIBUTES)
+//! attributes, stored inline. Cache operations use [`Tracer::cache_span`],
+//! which records the keys in [`attr`](crate::trace::attr). With the `otel`
+//! feature, finished spans are also exported through the OpenTelemetry
+//! tracer installed with `Tracer::export_to`, reusing the ids above so the
+//! nesting carries over. While exporting, a span that starts a new trace
+//! instead joins the active OpenTelemetry span, if any. Cache spans are only
+//! created while such a tracer is installed.
+
+use std::cell::Cell;
+use std::hash::{BuildHasher, RandomState};
+use std::marker::PhantomData;
+#[cfg(feature = "otel")]
+use std::time::SystemTime;
+
+thread_local! {
+    /// Context of the innermost active span on this thread.
+    static CURRENT: Cell<Option<TraceContext>> = const { Cell::new(None) };
+    /// Randomly keyed hasher turning [`ID_COUNT`] into ids.
+    static ID_KEYS: RandomState = RandomState::new();
+    /// Number of ids generated on this thread.
+    static ID_COUNT: Cell<u64> = const { Cell::new(0) };
+}
+
+/// A random, non-zero span id.
+///
+/// Ids hash a per-thread counter under per-thread random keys, so generating
+/// one touches no shared state.
+fn random_id() -> u64 {
+    loop {
+        let count = ID_COUNT.with(|c| c.replace(c.get().wrapping_add(1)));
+        let id = ID_KEYS.with(|keys| keys.hash_one(count));
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+/// A random, non-zero trace id.
+pub(crate) fn random_trace_id() -> u128 {
+    u128::from(random_id()) << 64 | u128::from(random_id())
+}
+
+/// Captured position within a trace.
+///
+/// The context is `Copy` and `Send`, so it can be handed to another thread and
+/// restored there; spans started under it join the same trace.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TraceContext {
+    trace_id: u128,
+    span_id: u64,
+}
+
+impl TraceContext {
+    /// Identifier shared by every span in the trace.
+    pub fn trace_id(&self) -> u128 {
+        self.trace_id
+    }
+
+    /// Identifier of the span this context points at.
+    pub fn span_id(&self) -> u64 {
+        self.span_id
+    }
+}
+
+/// Most attributes a single span records; further keys are ignored.
+pub const MAX_ATTRIBUTES: usize = 8;
+
+/// Attribute keys recorded on cache operation spans.
+pub mod attr {
+    /// Number of keys handled by a batch operation.
+    pub const BATCH_SIZE: &str = "cache.batch_size";
+    /// Whether the operation found the key.
+    pub const HIT: &str = "cache.hit";
+    /// Number of keys a batch lookup found.
+    pub const HITS: &str = "cache.hits";
+    /// Length of the key in bytes.
+    pub const KEY_LEN: &str = "cache.key_len";
+    /// Index of the shard owning the key.
+    pub const SHARD: &str = "cache.shard";
+}
+
+/// Value of a span attribute.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttributeValue {
+    /// A flag.
+    Bool(bool),
+    /// A signed integer.
+    Int(i64),
+    /// A static string.
+    Str(&'static str),
+}
+
+impl From<bool> for AttributeValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i64> for AttributeValue {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<usize> for AttributeValue {
+    fn from(value: usize) -> Self {
+        Self::Int(i64::try_from(value).unwrap_or(i64::MAX))
+    }
+}
+
+impl From<&'static str> for AttributeValue {
+    fn from(value: &'static str) -> Self {
+        Self::Str(value)
+    }
+}
+
 /// Tracing helper.
 pub struct Tracer;
+
+impl Tracer {
+    /// Start a span named `name` as a child of the current context.
+    ///
+    /// With no current context a new trace is started, or, while exporting,
+    /// the trace of the active OpenTelemetry span is joined. The span becomes
+    /// the current context until it is dropped.
+    pub fn span(name: &'static str) -> Span {
+        let parent = CURRENT.with(Cell::get);
+        #[cfg(feature = "otel")]
+        let (root_parent, trace_id) = match parent {
+            Some(parent) => (None, parent.trace_id),
+            None => crate::otel::root_parent(),
+        };
+        #[cfg(not(feature = "otel"))]
+        let trace_id = parent.map_or_else(random_trace_id, |p| p.trace_id);
+        let context = TraceContext {
+            trace_id,
+            span_id: random_id(),
+        };
+        CURRENT.with(|c| c.set(Some(context)));
+        Span {
+            name,
+            context,
+            parent_id: parent.map(|p| p.span_id),
+            previous: parent,
+            attributes: [("", AttributeValue::Bool(false)); MAX_ATTRIBUTES],
+            attribute_count: 0,
+            #[cfg(feature = "otel")]
+            root_parent,
+            #[cfg(feature = "otel")]
+            started: SystemTime::now(),
+            _thread_bound: PhantomData,
+        }
+    }
+
+    /// Start a span for cache operation `op` (such as `"get"`, `"put"` or
+    /// `"evict"`) on a key of `key_len` bytes owned by shard `shard`.
+    ///
+    /// Returns `None` unless [`Tracer::is_exporting`], so cache operations
+    /// pay nothing for spans nobody would see. Callers that learn whether the
+    /// key was found should record it under [`attr::HIT`].
+    pub fn cache_span(op: &'static str, key_len: usize, shard: usize) -> Option<Span> {
+        if !Self::is_exporting() {
+            return None;
+        }
+        let mut span = Self::span(op);
+        span.set_attribute(attr::KEY_LEN, key_len);
+        span.set_attribute(attr::SHARD, shard);
+        Some(span)
+    }
+
+    /// Start a span for batch operation `op` (such as `"get_many"`) on
+    /// `batch_size` keys owned by shard `shard`.
+    ///
+    /// Like [`Tracer::cache_span`], returns `None` unless exporting. Batch
+    /// lookups should record the number of keys found under [`attr::HITS`].
+    pub fn cache_batch_span(op: &'static str, batch_size: usize, shard: usize) -> Option<Span> {
+        if !Self::is_exporting() {
+            return None;
+        }
+        let mut span = Self::span(op);
+        span.set_attribute(attr::BATCH_SIZE, batch_size);
+        span.set_attribute(attr::SHARD, shard);
+        Some(span)
+    }
+
+    /// Whether finished spans are currently exported anywhere.
+    ///
+    /// Always `false` without the `otel` feature.
+    pub fn is_exporting() -> bool {
+        #[cfg(feature = "otel")]
+        return crate::otel::is_exporting();
+        #[cfg(not(feature = "otel"))]
+        false
+    }
+
+    /// Capture the context of the innermost active span on this thread.
+    pub fn current() -> Option<TraceContext> {
+        CURRENT.with(Cell::get)
+    }
+
+    /// Run `f` with `ctx` installed as the current context.
+    ///
+    /// Spans started inside `f` carry `ctx`'s trace id and use its span as
+    /// their parent. The previous context is restored afterwards, even if `f`
+    /// panics.
+    pub fn with_context<R>(ctx: TraceContext, f: impl FnOnce() -> R) -> R {
+        struct Restore(Option<TraceContext>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT.with(|c| c.set(self.0));
+            }
+        }
+
+        let _restore = Restore(CURRENT.with(|c| c.replace(Some(ctx))));
+        f()
+    }
+}
+
+/// An active span; ends when dropped.
+///
+/// Spans must be dropped in reverse order of creation on a given thread.
+/// Ending a span restores the thread's previous context, so a span cannot
+/// be sent to another thread; hand over its [`TraceContext`] instead.
+#[derive(Debug)]
+pub struct Span {
+    name: &'static str,
+    context: TraceContext,
+    parent_id: Option<u64>,
+    previous: Option<TraceContext>,
+    attributes: [(&'static str, AttributeValue); MAX_ATTRIBUTES],
+    attribute_count: usize,
+    /// OpenTelemetry context that was active when this span started a trace.
+    #[cfg(feature = "otel")]
+    root_parent: Option<opentelemetry::Context>,
+    #[cfg(feature = "otel")]
+    started: SystemTime,
+    /// Keeps the span on the thread whose context it changed.
+    _thread_bound: PhantomData<*const ()>,
+}
+
+impl Span {
+    /// Name given when the span was started.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Context identifying this span, suitable for passing to other threads.
+    pub fn context(&self) -> TraceContext {
+        self.context
+    }
+
+    /// Identifier of the trace this span belongs to.
+    pub fn trace_id(&self) -> u128 {
+        self.context.trace_id
+    }
+
+    /// Identifier of the span that was current when this one started.
+    pub fn parent_id(&self) -> Option<u64> {
+        self.parent_id
+    }
+
+    /// Record `value` under `key`, replacing any earlier value.
+    ///
+    /// A new key is ignored once the span holds [`MAX_ATTRIBUTES`].
+    pub fn set_attribute(&mut self, key: &'static str, value: impl Into<AttributeValue>) {
+        let value = value.into();
+        let count = self.attribute_count;
+        if let Some((_, existing)) = self.attributes[..count].iter_mut().find(|(k, _)| *k == key) {
+            *existing = value;
+        } else if count < MAX_ATTRIBUTES {
+            self.attributes[count] = (key, value);
+            self.attribute_count += 1;
+        }
+    }
+
+    /// Value recorded under `key`.
+    pub fn attribute(&self, key: &str) -> Option<AttributeValue> {
+        self.attributes()
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|&(_, value)| value)
+    }
+
+    /// Attributes in the order they were first recorded.
+    pub fn attributes(&self) -> &[(&'static str, AttributeValue)] {
+        &self.attributes[..self.attribute_count]
+    }
+
+    /// OpenTelemetry parent of a span that started a trace while exporting.
+    #[cfg(feature = "otel")]
+    pub(crate) fn root_parent(&self) -> Option<&opentelemetry::Context> {
+        self.root_parent.as_ref()
+    }
+
+    /// When the span was started.
+    #[cfg(feature = "otel")]
+    pub(crate) fn started(&self) -> SystemTime {
+        self.started
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        crate::otel::export(self);
+        CURRENT.with(|c| c.set(self.previous));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restored_context_continues_trace_on_another_thread() {
+        let root = Tracer::span("get");
+        let ctx = Tracer::current().expect("context");
+        assert_eq!(ctx, root.context());
+
+        let (trace_id, parent_id) = std::thread::spawn(move || {
+            assert!(Tracer::current().is_none());
+            Tracer::with_context(ctx, || {
+                let span = Tracer::span("load");
+                (span.trace_id(), span.parent_id())
+            })
+        })
+        .join()
+        .expect("thread");
+
+        assert_eq!(trace_id, root.trace_id());
+        assert_eq!(parent_id, Some(root.context().span_id()));
+
+        drop(root);
+        assert!(Tracer::current().is_none());
+        let unrelated = Tracer::span("put");
+        assert_ne!(unrelated.trace_id(), trace_id);
+        assert_eq!(unrelated.parent_id(), None);
+    }
+
+    #[test]
+    fn attributes_are_replaced_and_capped() {
+        let mut span = Tracer::span("get");
+        span.set_attribute(attr::KEY_LEN, 5usize);
+        span.set_attribute(attr::HIT, false);
+        span.set_attribute(attr::HIT, true);
+        assert_eq!(span.name(), "get");
+        assert_eq!(span.attribute(attr::KEY_LEN), Some(AttributeValue::Int(5)));
+        assert_eq!(span.attribute(attr::HIT), Some(AttributeValue::Bool(true)));
+        assert_eq!(span.attributes().len(), 2);
+
+        const EXTRA: [&str; MAX_ATTRIBUTES] = ["a", "b", "c", "d", "e", "f", "g", "h"];
+        for key in EXTRA {
+            span.set_attribute(key, 1i64);
+        }
+        assert_eq!(span.attributes().len(), MAX_ATTRIBUTES);
+        assert_eq!(span.attribute("f"), Some(AttributeValue::Int(1)));
+        assert_eq!(span.attribute("g"), None);
+    }
+
+    #[cfg(not(feature = "otel"))]
+    #[test]
+    fn cache_span_is_skipped_without_exporter() {
+        assert!(!Tracer::is_exporting());
+        assert!(Tracer::cache_span("get", 5, 3).is_none());
+        assert!(Tracer::cache_batch_span("get_many", 2, 3).is_none());
+    }
+}